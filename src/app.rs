@@ -1,19 +1,45 @@
+use crate::bookmarks::{self, Bookmark, Bookmarks};
+use crate::config;
+use crate::export::{self, Format};
+use crate::favorites::{self, Imported, Importer, List};
+use crate::html::{self, Span};
+use crate::keys::{self, Action, Keymap};
+use crate::mute::{self, MuteRules};
+use crate::palette::{Command, CommandPalette};
+use crate::settings::{self, LinkBehavior, Settings};
+use crate::theme::{self, Theme, UserThemes};
+use crate::timestamp::{self, Clock};
+use crate::watch::{self, WatchList};
+use eframe::egui::text::{LayoutJob, TextFormat};
 use eframe::{egui, epi};
 use hacker_news::model::firebase::Comment;
-use hacker_news::{client::json_client::JsonClient, model::firebase::Item, model::firebase::Story};
-use html_escape;
-use lazy_static::lazy_static;
-use regex::Regex;
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use hacker_news::{client::json_client::JsonClient, model::firebase::Item};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
 use std::thread;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use time_humanize::HumanTime;
 use url::Url;
 
-const BASE_URL: &'static str = "https://news.ycombinator.com";
-const REFETCH_DELAY_SECONDS: u64 = 60;
-const WINDOW: usize = 50;
+pub(crate) const BASE_URL: &str = "https://news.ycombinator.com";
+const PREFETCH_WORKERS: usize = 4;
+/// Longer titles are cut short in the thread tabs.
+const THREAD_TAB_TITLE_CHARS: usize = 32;
+/// How much of each comment's first line the thread outline shows.
+const OUTLINE_LINE_CHARS: usize = 80;
+/// Below this width the thread replaces the story list instead of sitting beside it.
+const SPLIT_MIN_WIDTH: f32 = 900.;
+/// Stories read or visited longer ago than this are forgotten, so the history doesn't grow
+/// forever.
+const READ_RETENTION_DAYS: u64 = 30;
+/// How long a view waits for its thread to load far enough to scroll back to where it was.
+const SCROLL_RESTORE_SECS: f64 = 10.;
+const SESSION_FILE: &str = "session.toml";
+/// How often the session file is rewritten while the reader runs; it is also saved on exit.
+const SESSION_SAVE_SECS: f64 = 60.;
 
 #[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
 struct Auth {
@@ -21,11 +47,75 @@ struct Auth {
     password: String,
 }
 
-#[derive(PartialEq)]
-enum Tab {
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub(crate) enum Tab {
     Top,
     New,
     Show,
+    Favorites,
+    Saved,
+}
+
+impl Tab {
+    pub(crate) const ALL: [Tab; 5] = [Tab::Top, Tab::New, Tab::Show, Tab::Favorites, Tab::Saved];
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Tab::Top => "Top",
+            Tab::New => "New",
+            Tab::Show => "Show",
+            Tab::Favorites => "Favorites",
+            Tab::Saved => "Saved",
+        }
+    }
+}
+
+/// How a thread's comments are laid out, chosen per thread.
+#[derive(Debug, PartialEq, Clone, Copy, serde::Deserialize, serde::Serialize)]
+enum CommentOrder {
+    /// Nested, in the order the site ranks them.
+    Ranked,
+    /// Nested, each level sorted newest first.
+    Newest,
+    Oldest,
+    /// Every comment in the thread in the order it was posted, without nesting.
+    Timeline,
+    /// Just the top-level comments, each linking to its replies.
+    Outline,
+}
+
+impl Default for CommentOrder {
+    fn default() -> Self {
+        CommentOrder::Ranked
+    }
+}
+
+impl CommentOrder {
+    const ALL: [CommentOrder; 5] = [
+        CommentOrder::Ranked,
+        CommentOrder::Newest,
+        CommentOrder::Oldest,
+        CommentOrder::Timeline,
+        CommentOrder::Outline,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            CommentOrder::Ranked => "Ranked",
+            CommentOrder::Newest => "Newest",
+            CommentOrder::Oldest => "Oldest",
+            CommentOrder::Timeline => "Timeline",
+            CommentOrder::Outline => "Top level only",
+        }
+    }
+
+    /// The order for a single comment's replies, which are always shown nested.
+    fn nested(self) -> Self {
+        match self {
+            CommentOrder::Timeline | CommentOrder::Outline => CommentOrder::Ranked,
+            order => order,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -40,11 +130,72 @@ struct Data {
     show: HashMap<usize, Item>,
     show_ids: Vec<u32>,
     show_page: usize,
+    /// Only changed through `set_comment`, which keeps `subtree_sizes` in step.
     comments: HashMap<u32, CommentState>,
+    /// What `subtree_size` has worked out since the last comment changed, by comment id.
+    subtree_sizes: HashMap<u32, (usize, usize)>,
+    /// What `comment_times` has worked out since the last comment changed, by story id.
+    comment_times: HashMap<u32, Vec<u64>>,
+    items: HashMap<u32, ItemState>,
+    /// A copy of the watch list, so the feed threads can check stories as they arrive.
+    watch_terms: Vec<String>,
+    alerts: Vec<Alert>,
+    /// Stories that have already raised an alert, so refreshes don't repeat it, with when they
+    /// last turned up in a feed. They are forgotten a while after leaving the feeds.
+    alerted: HashMap<u32, u64>,
+    /// Feeds fetched at least once since startup. Matches in a feed's first fetch are only
+    /// noted, so that stories already there don't all raise alerts at once.
+    watch_seeded: HashSet<Tab>,
+    /// Stories from the imported favorites and upvoted lists, by id.
+    imported: HashMap<u32, Item>,
+    favorite_ids: Vec<u32>,
+    upvoted_ids: Vec<u32>,
+    importing: bool,
+    import_status: Option<String>,
+    /// Copied from the settings for the feed threads.
+    refresh_seconds: u64,
+    page_size: usize,
+}
+
+/// A watched term turning up in one of the feeds.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+struct Alert {
+    id: u32,
+    title: String,
+    term: String,
+    /// When the story was first seen, in seconds since the epoch.
+    time: u64,
+}
+
+/// What the reader remembers between runs, kept in its own data file like the bookmarks so it
+/// survives without the `persistence` feature.
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+struct Session {
+    // TOML wants plain values before tables, so the maps come last and empty lists of tables,
+    // which would be written as plain values, are left out.
+    active_thread: Option<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    alerts: Vec<Alert>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    thread_tabs: Vec<ThreadTab>,
+    #[serde(with = "config::string_keys")]
+    alerted: HashMap<u32, u64>,
+    #[serde(with = "config::string_keys")]
+    read: HashMap<u32, u64>,
+    #[serde(with = "config::string_keys")]
+    visits: HashMap<u32, Visit>,
+    #[serde(with = "config::string_keys")]
+    open_state: HashMap<u32, (bool, u64)>,
+    #[serde(with = "config::string_keys")]
+    scroll_offsets: HashMap<ScrollKey, (f32, u64)>,
+    #[serde(with = "config::string_keys")]
+    comment_orders: HashMap<u32, CommentOrder>,
 }
 
 impl Data {
     fn new() -> Self {
+        let settings = Settings::default();
         Self {
             top: HashMap::new(),
             top_ids: Vec::new(),
@@ -56,8 +207,575 @@ impl Data {
             show_ids: Vec::new(),
             show_page: 0,
             comments: HashMap::new(),
+            subtree_sizes: HashMap::new(),
+            comment_times: HashMap::new(),
+            items: HashMap::new(),
+            watch_terms: Vec::new(),
+            alerts: Vec::new(),
+            alerted: HashMap::new(),
+            watch_seeded: HashSet::new(),
+            imported: HashMap::new(),
+            favorite_ids: Vec::new(),
+            upvoted_ids: Vec::new(),
+            importing: false,
+            import_status: None,
+            refresh_seconds: settings.refresh_seconds,
+            page_size: settings.page_size,
+        }
+    }
+
+    fn imported_ids(&mut self, list: List) -> &mut Vec<u32> {
+        match list {
+            List::Favorites => &mut self.favorite_ids,
+            List::Upvoted => &mut self.upvoted_ids,
+        }
+    }
+
+    /// A loaded story from any feed, imported list, or opened directly.
+    fn story(&self, id: u32) -> Option<LocalStory> {
+        if let Some(ItemState::Loaded(story)) = self.items.get(&id) {
+            return Some(story.clone());
+        }
+        [&self.top, &self.new, &self.show]
+            .iter()
+            .flat_map(|feed| feed.values())
+            .chain(self.imported.get(&id))
+            .filter_map(LocalStory::from_item)
+            .find(|story| story.id == id)
+    }
+
+    /// An imported list in the same shape as a feed, skipping stories that haven't loaded.
+    fn imported_stories(&self, list: List) -> Vec<(usize, LocalStory)> {
+        let ids = match list {
+            List::Favorites => &self.favorite_ids,
+            List::Upvoted => &self.upvoted_ids,
+        };
+        ids.iter()
+            .enumerate()
+            .filter_map(|(idx, id)| {
+                let story = LocalStory::from_item(self.imported.get(id)?)?;
+                Some((idx, story))
+            })
+            .collect()
+    }
+
+    /// Raises an alert the first time a story matching the watch list shows up in `feed`.
+    fn check_watch(&mut self, item: &Item, feed: Tab) {
+        let story = match LocalStory::from_item(item) {
+            Some(story) => story,
+            None => return,
+        };
+        let title = story.title.unwrap_or_default();
+        if let Some(term) = watch::matching_term(&self.watch_terms, &title) {
+            let time = unix_now();
+            if self.alerted.insert(story.id, time).is_none() && self.watch_seeded.contains(&feed) {
+                self.alerts.push(Alert {
+                    id: story.id,
+                    term: term.to_string(),
+                    title,
+                    time,
+                });
+            }
+        }
+    }
+
+    fn set_comment(&mut self, id: u32, state: CommentState) {
+        // Until a comment loads it counts the same whatever its state.
+        if matches!(state, CommentState::Loaded(_)) {
+            self.subtree_sizes.clear();
+            self.comment_times.clear();
+        }
+        self.comments.insert(id, state);
+    }
+
+    /// How many comments are known to exist below `kids`, counting `kids` themselves, and how
+    /// many of those have loaded, as far as the tree has been fetched.
+    fn thread_size(&mut self, kids: &[u32]) -> (usize, usize) {
+        kids.iter().fold((0, 0), |(known, loaded), id| {
+            let (k, l) = self.subtree_size(*id);
+            (known + k, loaded + l)
+        })
+    }
+
+    /// `thread_size` for a single comment and its replies. Sizes are kept until the next
+    /// comment changes, so a frame drawing many replies walks each subtree only once.
+    fn subtree_size(&mut self, id: u32) -> (usize, usize) {
+        if let Some(size) = self.subtree_sizes.get(&id) {
+            return *size;
+        }
+        let kids = match self.comments.get(&id) {
+            Some(CommentState::Loaded(c)) => c.kids.clone().unwrap_or_default(),
+            _ => return (1, 0),
+        };
+        let (known, loaded) = self.thread_size(&kids);
+        let size = (known + 1, loaded + 1);
+        self.subtree_sizes.insert(id, size);
+        size
+    }
+
+    /// `kids` in the given order. Comments that haven't loaded yet keep their place after
+    /// those that have, since we don't know when they were posted.
+    fn sorted_kids(&self, kids: &[u32], order: CommentOrder) -> Vec<u32> {
+        let mut kids = kids.to_vec();
+        let time = |id: &u32| match self.comments.get(id) {
+            Some(CommentState::Loaded(c)) => Some(c.time),
+            _ => None,
+        };
+        match order {
+            CommentOrder::Newest => {
+                kids.sort_by_key(|id| (time(id).is_none(), std::cmp::Reverse(time(id))))
+            }
+            CommentOrder::Oldest => kids.sort_by_key(|id| (time(id).is_none(), time(id))),
+            _ => {}
+        }
+        kids
+    }
+
+    /// When each loaded comment below `story`'s `kids` was posted, oldest first.
+    fn comment_times(&mut self, story: u32, kids: &[u32]) -> &[u64] {
+        let comments = &self.comments;
+        self.comment_times.entry(story).or_insert_with(|| {
+            let mut times = Vec::new();
+            let mut stack: Vec<u32> = kids.to_vec();
+            while let Some(id) = stack.pop() {
+                if let Some(CommentState::Loaded(c)) = comments.get(&id) {
+                    stack.extend(c.kids.as_deref().unwrap_or_default());
+                    times.push(c.time);
+                }
+            }
+            times.sort_unstable();
+            times
+        })
+    }
+
+    /// Every loaded comment below `kids`, oldest first.
+    fn timeline(&self, kids: &[u32]) -> Vec<LocalComment> {
+        let mut comments = Vec::new();
+        let mut stack: Vec<u32> = kids.to_vec();
+        while let Some(id) = stack.pop() {
+            if let Some(CommentState::Loaded(c)) = self.comments.get(&id) {
+                stack.extend(c.kids.as_deref().unwrap_or_default());
+                comments.push(c.clone());
+            }
+        }
+        comments.sort_by_key(|c| (c.time, c.id));
+        comments
+    }
+}
+
+/// When the user looked at a story's comments, so a later visit can point out new ones. A
+/// comment is new if it was posted after the last visit began, whether or not it was ever
+/// scrolled to or expanded.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+struct Visit {
+    /// When the current (or most recent) visit began.
+    started: u64,
+    /// When the visit before that began. Nothing is new on a first visit.
+    previous: Option<u64>,
+}
+
+impl Visit {
+    fn begin(&mut self, now: u64) {
+        self.previous = Some(self.started);
+        self.started = now;
+    }
+
+    /// Whether a comment posted at `time` is new to the visit under way.
+    fn is_new(&self, time: u64) -> bool {
+        self.previous.map_or(false, |previous| time > previous)
+    }
+
+    /// How many of a thread's comments, posted at the sorted `times`, are new. While the
+    /// thread is open that's since the visit before this one; once it's closed, everything
+    /// up to this visit has been seen.
+    fn new_count(&self, times: &[u64], open: bool) -> usize {
+        let since = if open {
+            self.previous
+        } else {
+            Some(self.started)
+        };
+        since.map_or(0, |since| {
+            times.len() - times.partition_point(|time| *time <= since)
+        })
+    }
+}
+
+/// A thread opened in its own tab, beside the feed tabs.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+struct ThreadTab {
+    /// The items viewed in this tab, most recent last, like `YReader::history`.
+    history: Vec<u32>,
+    /// Filled in once the first item has loaded, and kept so restored tabs have names.
+    title: Option<String>,
+}
+
+/// A scrolling view whose position is remembered: a feed's story list, or the thread rooted
+/// at an item.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum ScrollKey {
+    Feed(Tab),
+    Item(u32),
+}
+
+/// Written as `feed:Top` or `item:123`, to key the session file's table of offsets.
+impl fmt::Display for ScrollKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScrollKey::Feed(tab) => write!(f, "feed:{}", tab.name()),
+            ScrollKey::Item(id) => write!(f, "item:{}", id),
+        }
+    }
+}
+
+impl FromStr for ScrollKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("feed", name)) => Tab::ALL
+                .into_iter()
+                .find(|tab| tab.name() == name)
+                .map(ScrollKey::Feed)
+                .ok_or_else(|| format!("unknown feed \"{}\"", name)),
+            Some(("item", id)) => id
+                .parse()
+                .map(ScrollKey::Item)
+                .map_err(|e| format!("{}", e)),
+            _ => Err(format!("unknown view \"{}\"", s)),
+        }
+    }
+}
+
+/// A story or comment that was rendered this frame and can be selected from the keyboard.
+#[derive(Clone)]
+struct NavEntry {
+    id: u32,
+    /// Zero for stories, and one more than the nesting level for comments.
+    depth: usize,
+    link: Option<String>,
+    /// A comment new since the last visit to its thread.
+    new: bool,
+}
+
+/// Fetches a single comment into `data`, returning the ids of its replies.
+fn fetch_comment(client: &JsonClient, data: &Mutex<Data>, id: u32) -> Option<Vec<u32>> {
+    let comment = client.item(id);
+
+    let mut data = data.lock().unwrap();
+    match comment {
+        Ok(Item::Comment(c)) => {
+            let kids = c.kids.clone();
+            data.set_comment(id, CommentState::Loaded(LocalComment::from_lib(&c)));
+            kids
+        }
+        _ => {
+            data.set_comment(id, CommentState::Errored);
+            None
+        }
+    }
+}
+
+struct PrefetchQueue {
+    pending: VecDeque<u32>,
+    in_flight: usize,
+    /// Worker threads still running. They stop once the queue runs dry, and `enqueue` starts
+    /// them again.
+    workers: usize,
+}
+
+/// A pool of worker threads fetching comments, either whole threads or just the comments it
+/// is handed.
+struct Prefetch {
+    data: Arc<Mutex<Data>>,
+    /// Whether the replies of each fetched comment are fetched too.
+    whole_threads: bool,
+    cancel: Arc<AtomicBool>,
+    queue: Arc<Mutex<PrefetchQueue>>,
+}
+
+impl Prefetch {
+    fn new(data: &Arc<Mutex<Data>>, whole_threads: bool) -> Self {
+        Self {
+            data: Arc::clone(data),
+            whole_threads,
+            cancel: Arc::new(AtomicBool::new(false)),
+            queue: Arc::new(Mutex::new(PrefetchQueue {
+                pending: VecDeque::new(),
+                in_flight: 0,
+                workers: 0,
+            })),
+        }
+    }
+
+    /// Fetches every comment below `kids` breadth-first, so that top-level comments
+    /// arrive before deeper replies. Workers stop early once the prefetch is cancelled.
+    fn start(data: &Arc<Mutex<Data>>, kids: &[u32]) -> Self {
+        let prefetch = Self::new(data, true);
+        prefetch.enqueue(kids);
+        prefetch
+    }
+
+    /// Queues `ids` for fetching, skipping any already queued.
+    fn enqueue(&self, ids: &[u32]) {
+        let mut queue = self.queue.lock().unwrap();
+        for id in ids {
+            if !queue.pending.contains(id) {
+                queue.pending.push_back(*id);
+            }
+        }
+        while queue.workers < PREFETCH_WORKERS {
+            queue.workers += 1;
+            self.spawn_worker();
+        }
+    }
+
+    fn spawn_worker(&self) {
+        let data = Arc::clone(&self.data);
+        let cancel = Arc::clone(&self.cancel);
+        let queue = Arc::clone(&self.queue);
+        let whole_threads = self.whole_threads;
+
+        thread::spawn(move || {
+            let client = JsonClient::new();
+            while !cancel.load(Ordering::Relaxed) {
+                let next = {
+                    let mut queue = queue.lock().unwrap();
+                    let next = queue.pending.pop_front();
+                    if next.is_some() {
+                        queue.in_flight += 1;
+                    } else if queue.in_flight == 0 {
+                        // Leaving under the lock, so `enqueue` can't count on this worker.
+                        queue.workers -= 1;
+                        return;
+                    }
+                    next
+                };
+
+                let id = match next {
+                    Some(id) => id,
+                    None => {
+                        // Another worker may still turn up more replies.
+                        thread::sleep(Duration::from_millis(50));
+                        continue;
+                    }
+                };
+
+                // Claimed under one lock, so no two workers or pools fetch the same comment.
+                let state = {
+                    let mut data = data.lock().unwrap();
+                    let state = data.comments.get(&id).cloned();
+                    if state.is_none() {
+                        data.set_comment(id, CommentState::Loading);
+                    }
+                    state
+                };
+                let kids = match state {
+                    Some(CommentState::Loaded(c)) => c.kids,
+                    Some(CommentState::Errored) => None,
+                    Some(CommentState::Loading) => {
+                        // Already being fetched elsewhere; check back once it lands.
+                        thread::sleep(Duration::from_millis(50));
+                        Some(vec![id])
+                    }
+                    None => fetch_comment(&client, &data, id),
+                };
+
+                let mut queue = queue.lock().unwrap();
+                queue.in_flight -= 1;
+                if whole_threads {
+                    queue.pending.extend(kids.unwrap_or_default());
+                }
+            }
+            queue.lock().unwrap().workers -= 1;
+        });
+    }
+
+    fn is_done(&self) -> bool {
+        let queue = self.queue.lock().unwrap();
+        queue.pending.is_empty() && queue.in_flight == 0
+    }
+
+    fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+/// A thread whose comments are still being fetched so that it can be exported whole.
+struct PendingExport {
+    story: LocalStory,
+    format: Format,
+    prefetch: Prefetch,
+}
+
+/// Fetches an item of unknown type into `data`.
+fn fetch_item(client: &JsonClient, data: &Mutex<Data>, id: u32) {
+    let item = client.item(id);
+
+    let mut data = data.lock().unwrap();
+    data.items.remove(&id);
+    match item {
+        Ok(Item::Comment(c)) => {
+            data.set_comment(id, CommentState::Loaded(LocalComment::from_lib(&c)));
+        }
+        Ok(item) => match LocalStory::from_item(&item) {
+            Some(s) => {
+                data.items.insert(id, ItemState::Loaded(s));
+            }
+            None => {
+                data.items.insert(id, ItemState::Errored);
+            }
+        },
+        Err(_) => {
+            data.items.insert(id, ItemState::Errored);
+        }
+    }
+}
+
+/// A comment's first line, cut short for the thread outline.
+fn outline_line(text: Option<&str>) -> String {
+    let line = text.map(html::first_line).unwrap_or_default();
+    let mut label: String = line.chars().take(OUTLINE_LINE_CHARS).collect();
+    if label.len() < line.len() {
+        label.push('…');
+    }
+    label
+}
+
+/// A feed's stories in the order they're ranked.
+fn feed_stories(items: &HashMap<usize, Item>) -> Vec<(usize, LocalStory)> {
+    let mut stories: Vec<(usize, LocalStory)> = items
+        .iter()
+        .filter_map(|(idx, i)| LocalStory::from_item(i).map(|s| (*idx, s)))
+        .collect();
+    stories.sort_by_key(|(idx, _)| *idx);
+    stories
+}
+
+/// Fetches whichever of the imported `ids` haven't been loaded yet.
+fn fetch_imported(client: &JsonClient, data: &Mutex<Data>, ids: &[u32]) {
+    for id in ids {
+        if data.lock().unwrap().imported.contains_key(id) {
+            continue;
+        }
+        if let Ok(item) = client.item(*id) {
+            data.lock().unwrap().imported.insert(*id, item);
+        }
+    }
+}
+
+/// Renders the HTML of a comment or story text. With `in_app`, plain clicks on links to other
+/// items on the site are kept in-app, and the clicked item's id returned for the caller to open.
+fn render_html(ui: &mut egui::Ui, text: &str, in_app: bool) -> Option<u32> {
+    let mut opened = None;
+
+    ui.horizontal_wrapped(|ui| {
+        ui.spacing_mut().item_spacing.y = 10.;
+        for paragraph in html::parse(text) {
+            for span in paragraph {
+                match span {
+                    Span::Text(text) => {
+                        ui.label(text);
+                    }
+                    Span::Link { url, label } => {
+                        let link = ui.hyperlink_to(label, &url);
+                        let plain_click = in_app && link.clicked() && !ui.input().modifiers.any();
+                        if let Some(id) = parse_item_id(&url).filter(|_| plain_click) {
+                            ui.ctx().output().open_url = None;
+                            opened = Some(id);
+                        }
+                    }
+                }
+            }
+
+            ui.end_row();
+        }
+    });
+
+    opened
+}
+
+/// Accepts either a bare item id or a link to an item on the site.
+fn parse_item_id(input: &str) -> Option<u32> {
+    let input = input.trim();
+    if let Ok(id) = input.parse() {
+        return Some(id);
+    }
+
+    // Pasted links often leave out the scheme, which would make the host look like a path.
+    let host = input
+        .split(|c| c == '/' || c == '?')
+        .next()
+        .unwrap_or_default();
+    let input = if !input.contains("://") && host.contains('.') {
+        format!("https://{}", input)
+    } else {
+        input.to_string()
+    };
+
+    // Links inside comment text are sometimes relative to the site root.
+    let url = Url::parse(BASE_URL).ok()?.join(&input).ok()?;
+    if url.domain() != Some("news.ycombinator.com") || url.path() != "/item" {
+        return None;
+    }
+    url.query_pairs()
+        .find(|(key, _)| key == "id")
+        .and_then(|(_, id)| id.parse().ok())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("oops")
+        .as_secs()
+}
+
+/// Byte ranges of every occurrence of `query` in `text`, ignoring ASCII case. Matching
+/// bytes rather than chars keeps the ranges on char boundaries without re-casing the text.
+fn find_matches(text: &str, query: &str) -> Vec<Range<usize>> {
+    let (text, query) = (text.as_bytes(), query.as_bytes());
+    let mut matches = Vec::new();
+    if query.is_empty() {
+        return matches;
+    }
+
+    let mut start = 0;
+    while start + query.len() <= text.len() {
+        if text[start..start + query.len()].eq_ignore_ascii_case(query) {
+            matches.push(start..start + query.len());
+            start += query.len();
+        } else {
+            start += 1;
         }
     }
+    matches
+}
+
+/// Lays out `text` in `color`, with every match of `query` drawn on a highlighted background.
+fn highlighted(
+    text: &str,
+    query: &str,
+    color: egui::Color32,
+    highlight: egui::Color32,
+) -> LayoutJob {
+    let format = TextFormat {
+        style: egui::TextStyle::Body,
+        color,
+        ..Default::default()
+    };
+    let marked = TextFormat {
+        background: highlight,
+        ..format
+    };
+
+    let mut job = LayoutJob::default();
+    let mut end = 0;
+    for range in find_matches(text, query) {
+        job.append(&text[end..range.start], 0.0, format);
+        job.append(&text[range.clone()], 0.0, marked);
+        end = range.end;
+    }
+    job.append(&text[end..], 0.0, format);
+    job
 }
 
 #[derive(Debug, Clone)]
@@ -68,29 +786,114 @@ enum CommentState {
     Errored,
 }
 
+/// Items opened directly by id rather than through a feed. Until one has loaded we don't
+/// know what type it is; comments are filed in `Data::comments`, everything else here.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+enum ItemState {
+    Loading,
+    Loaded(LocalStory),
+    Errored,
+}
+
+#[derive(Debug, Clone)]
 #[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
 struct LocalStory {
     id: hacker_news::model::Id,
     by: Option<String>,
     time: u64,
     kids: Option<Vec<hacker_news::model::Id>>,
+    descendants: Option<u32>,
     score: Option<hacker_news::model::Score>,
     title: Option<String>,
     url: Option<String>,
+    text: Option<String>,
+    parts: Option<Vec<hacker_news::model::Id>>,
 }
 
 impl LocalStory {
-    fn from_lib(story: &Story) -> Self {
-        Self {
-            id: story.id.clone(),
-            by: story.by.clone(),
-            time: story.time,
-            kids: story.kids.clone(),
-            score: story.score.clone(),
-            title: story.title.clone(),
-            url: story.url.clone(),
+    /// Stories, jobs, polls and poll options all render the same way; comments don't.
+    fn from_item(item: &Item) -> Option<Self> {
+        match item {
+            Item::Story(story) => Some(Self {
+                id: story.id,
+                by: story.by.clone(),
+                time: story.time,
+                kids: story.kids.clone(),
+                descendants: story.descendants,
+                score: story.score,
+                title: story.title.clone(),
+                url: story.url.clone(),
+                text: None,
+                parts: None,
+            }),
+            Item::Job(job) => Some(Self {
+                id: job.id,
+                by: job.by.clone(),
+                time: job.time,
+                kids: job.kids.clone(),
+                descendants: None,
+                score: None,
+                title: Some(job.title.clone()),
+                url: job.url.clone(),
+                text: job.text.clone(),
+                parts: None,
+            }),
+            Item::Poll(poll) => Some(Self {
+                id: poll.id,
+                by: poll.by.clone(),
+                time: poll.time,
+                kids: poll.kids.clone(),
+                descendants: poll.descendants,
+                score: poll.score,
+                title: poll.title.clone(),
+                url: None,
+                text: poll.text.clone(),
+                parts: poll.parts.clone(),
+            }),
+            Item::PollOption(opt) => Some(Self {
+                id: opt.id,
+                by: opt.by.clone(),
+                time: opt.time,
+                kids: opt.kids.clone(),
+                descendants: None,
+                score: opt.score,
+                title: None,
+                url: None,
+                text: None,
+                parts: None,
+            }),
+            Item::Comment(_) => None,
+        }
+    }
+
+    fn bookmark(&self) -> Bookmark {
+        Bookmark {
+            id: self.id,
+            saved: unix_now(),
+            tags: Vec::new(),
+            by: self.by.clone(),
+            time: self.time,
+            title: self.title.clone(),
+            url: self.url.clone(),
+            text: self.text.clone(),
         }
     }
+
+    /// The site a story links to, as shown next to its title.
+    fn domain(&self) -> Option<String> {
+        let url = Url::parse(self.url.as_deref()?).ok()?;
+        url.domain().map(|d| d.to_string())
+    }
+
+    /// Whether the title, site or author contains `query`. Everything matches an empty query.
+    fn matches(&self, query: &str) -> bool {
+        query.is_empty()
+            || [self.title.clone(), self.domain(), self.by.clone()]
+                .iter()
+                .flatten()
+                .any(|field| !find_matches(field, query).is_empty())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -100,19 +903,36 @@ struct LocalComment {
     by: Option<String>,
     time: u64,
     kids: Option<Vec<hacker_news::model::Id>>,
-    // parent: Option<hacker_news::model::Id>,
+    parent: Option<hacker_news::model::Id>,
     text: Option<String>,
+    deleted: bool,
+    dead: bool,
 }
 
 impl LocalComment {
     fn from_lib(comment: &Comment) -> Self {
         Self {
-            id: comment.id.clone(),
+            id: comment.id,
             by: comment.by.clone(),
             time: comment.time,
             kids: comment.kids.clone(),
-            // parent: comment.parent.clone(),
+            parent: comment.parent,
             text: comment.text.clone(),
+            deleted: comment.deleted.unwrap_or_default(),
+            dead: comment.dead.unwrap_or_default(),
+        }
+    }
+
+    fn bookmark(&self) -> Bookmark {
+        Bookmark {
+            id: self.id,
+            saved: unix_now(),
+            tags: Vec::new(),
+            by: self.by.clone(),
+            time: self.time,
+            title: None,
+            url: None,
+            text: self.text.clone(),
         }
     }
 }
@@ -125,221 +945,1836 @@ pub struct YReader {
     authed: bool,
     show_login: bool,
     show_settings: bool,
+    /// Stories the user has opened, with when they first did so. Kept in the session file.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    read: HashMap<u32, u64>,
+    hide_read: bool,
+    /// Keyed by story id. Kept in the session file.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    visits: HashMap<u32, Visit>,
+    /// Threads and reply lists the user opened or closed, by item id, with when they did so.
+    /// Anything not in here starts out as the settings say. Kept in the session file.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    open_state: HashMap<u32, (bool, u64)>,
+    /// Threads shown in anything other than ranked order, by story id. Kept in the session
+    /// file.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    comment_orders: HashMap<u32, CommentOrder>,
+    /// Each open timeline's comment ids in posting order, by story id, with how many comments
+    /// had loaded when it was built.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    timelines: HashMap<u32, (usize, Vec<u32>)>,
+    /// How far each view was scrolled, with when it was last looked at. Kept in the session
+    /// file.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    scroll_offsets: HashMap<ScrollKey, (f32, u64)>,
+    /// Views being put back where they were left, with when that started. Each holds its
+    /// offset until enough has loaded to reach it, or the user scrolls.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    scroll_restores: HashMap<ScrollKey, f64>,
+    /// The views drawn last frame and so far this frame; one that wasn't drawn last frame is
+    /// being returned to.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    shown_views: (Vec<ScrollKey>, Vec<ScrollKey>),
     // this how you opt-out of serialization of a member
     #[cfg_attr(feature = "persistence", serde(skip))]
     tab: Tab,
+    /// Items opened as the root of a view, most recent last. Empty while browsing a feed.
+    /// This is the active thread tab's history; opening an item from a feed opens a tab.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    history: Vec<u32>,
+    /// Kept in the session file, with `active_thread`.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    thread_tabs: Vec<ThreadTab>,
+    /// Which thread tab is showing, if any, rather than a feed.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    active_thread: Option<usize>,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    open_input: String,
     data: Arc<Mutex<Data>>,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    prefetches: HashMap<u32, Prefetch>,
+    /// Fetches comments as they come into view, when no prefetch has got to them yet.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    comment_loader: Option<Prefetch>,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    show_help: bool,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    palette: CommandPalette,
+    /// Filters the story list; applied to every loaded feed, so it survives refreshes.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    filter: String,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    focus_search: bool,
+    /// Loaded from its own config file, like the keymap.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    mutes: MuteRules,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    mutes_error: Option<String>,
+    /// Shows muted stories and comments instead of hiding them.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    show_muted: bool,
+    /// Loaded from its own config file; the feed threads see it through `Data::watch_terms`.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    watch: WatchList,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    watch_error: Option<String>,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    show_alerts: bool,
+    /// Stories whose comments were on screen last frame; opening one starts a new visit.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    open_stories: HashSet<u32>,
+    /// Threads drawn this frame, with their top-level comments, gathered from every pane.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    open_threads: Vec<(u32, Vec<u32>)>,
+    /// Whether the story list and the open thread are side by side this frame.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    split: bool,
+    /// Run at the start of next frame, once the layout it navigates has been built.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    queued_action: Option<Action>,
+    /// Kept in their own file so they survive without the `persistence` feature.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    bookmarks: Bookmarks,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    bookmarks_error: Option<String>,
+    saved_tag: Option<String>,
+    saved_oldest_first: bool,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    tag_drafts: HashMap<u32, String>,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    export_status: Option<(u32, String)>,
+    /// Keyed by story id.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    exports: HashMap<u32, PendingExport>,
+    /// The format last picked from an "Export" menu, which the keyboard shortcut uses too.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    export_format: Format,
+    import_username: String,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    imported_list: List,
+    /// Loaded from its own config file, like the keymap.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    settings: Settings,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    settings_error: Option<String>,
+    /// The settings as last applied to the context and feed threads.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    applied: Option<Settings>,
+    /// Built-in themes and any from the themes file.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    themes: Vec<Theme>,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    themes_error: Option<String>,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    fonts_error: Option<String>,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    session_error: Option<String>,
+    /// When the session file was last written, in `egui` input time.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    session_saved: f64,
+    /// Read once at the start of each frame, for every timestamp drawn in it.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    clock: Clock,
+    /// The display's own scaling, which the interface scale multiplies.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    native_pixels_per_point: f32,
+    /// Notified to wake the feed threads for an early refresh.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    refresh: Arc<Condvar>,
+    /// Loaded from its own config file rather than the app state, so it can be hand-edited.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    keymap: Keymap,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    keymap_error: Option<String>,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    recording: Option<Action>,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    selected: Option<u32>,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    scroll_to_selected: bool,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    toggle_request: Option<u32>,
+    /// Reply lists to force open or shut when next drawn, from collapsing or expanding a
+    /// whole subtree.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    header_requests: HashMap<u32, bool>,
+    /// The outline's line for each comment, by id, so it isn't worked out from the HTML every
+    /// frame.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    outline_lines: HashMap<u32, String>,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    nav_order: Vec<NavEntry>,
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    expanded: HashSet<u32>,
 }
 
 impl YReader {
     fn init(&self) {
         let data_top = Arc::clone(&self.data);
+        let refresh_top = Arc::clone(&self.refresh);
         thread::spawn(move || loop {
             let client = JsonClient::new();
             let ids = client.top_stories();
             if let Ok(ids) = ids {
-                let page;
+                let (page, page_size);
                 {
                     let data = data_top.lock().unwrap();
                     page = data.top_page;
+                    page_size = data.page_size;
                 }
-                for (idx, id) in ids.iter().take(WINDOW * (page + 1)).enumerate() {
+                for (idx, id) in ids.iter().take(page_size * (page + 1)).enumerate() {
                     if let Ok(item) = client.item(*id) {
                         let mut data = data_top.lock().unwrap();
+                        data.check_watch(&item, Tab::Top);
                         data.top.insert(idx, item);
                     }
                 }
                 let mut data = data_top.lock().unwrap();
                 data.top_ids = ids;
+                data.watch_seeded.insert(Tab::Top);
                 data.top_page = (data.top_page + 1) % 2;
             }
-            thread::sleep(Duration::from_secs(REFETCH_DELAY_SECONDS));
+            let data = data_top.lock().unwrap();
+            let delay = Duration::from_secs(data.refresh_seconds);
+            let _ = refresh_top.wait_timeout(data, delay);
         });
 
         let data_new = Arc::clone(&self.data);
+        let refresh_new = Arc::clone(&self.refresh);
         thread::spawn(move || loop {
             let client = JsonClient::new();
             let ids = client.new_stories();
             if let Ok(ids) = ids {
-                let page;
+                let (page, page_size);
                 {
                     let data = data_new.lock().unwrap();
                     page = data.new_page;
+                    page_size = data.page_size;
                 }
-                for (idx, id) in ids.iter().take(WINDOW * (page + 1)).enumerate() {
+                for (idx, id) in ids.iter().take(page_size * (page + 1)).enumerate() {
                     if let Ok(item) = client.item(*id) {
                         let mut data = data_new.lock().unwrap();
+                        data.check_watch(&item, Tab::New);
                         data.new.insert(idx, item);
                     }
                 }
-                let mut data = data_new.lock().unwrap();
-                data.new_ids = ids;
-                data.new_page = (data.new_page + 1) % 2;
+                let mut data = data_new.lock().unwrap();
+                data.new_ids = ids;
+                data.watch_seeded.insert(Tab::New);
+                data.new_page = (data.new_page + 1) % 2;
+            }
+            let data = data_new.lock().unwrap();
+            let delay = Duration::from_secs(data.refresh_seconds);
+            let _ = refresh_new.wait_timeout(data, delay);
+        });
+
+        let data_show = Arc::clone(&self.data);
+        let refresh_show = Arc::clone(&self.refresh);
+        thread::spawn(move || loop {
+            let client = JsonClient::new();
+            let ids = client.show_stories();
+            if let Ok(ids) = ids {
+                let (page, page_size);
+                {
+                    let data = data_show.lock().unwrap();
+                    page = data.show_page;
+                    page_size = data.page_size;
+                }
+                for (idx, id) in ids.iter().take(page_size * (page + 1)).enumerate() {
+                    if let Ok(item) = client.item(*id) {
+                        let mut data = data_show.lock().unwrap();
+                        data.check_watch(&item, Tab::Show);
+                        data.show.insert(idx, item);
+                    }
+                }
+                let mut data = data_show.lock().unwrap();
+                data.show_ids = ids;
+                data.watch_seeded.insert(Tab::Show);
+                data.show_page = (data.show_page + 1) % 2;
+            }
+            let data = data_show.lock().unwrap();
+            let delay = Duration::from_secs(data.refresh_seconds);
+            let _ = refresh_show.wait_timeout(data, delay);
+        });
+    }
+
+    fn handle_keys(&mut self, ctx: &egui::CtxRef) {
+        if self.recording.is_some() {
+            return;
+        }
+
+        if let Some(action) = self.queued_action.take() {
+            self.run_action(ctx, action);
+        }
+        for action in self.keymap.pressed_actions(ctx) {
+            self.run_action(ctx, action);
+        }
+    }
+
+    fn run_action(&mut self, ctx: &egui::CtxRef, action: Action) {
+        // Selection moves through the items as they were laid out last frame.
+        let position = self
+            .selected
+            .and_then(|id| self.nav_order.iter().position(|e| e.id == id));
+
+        match action {
+            Action::SelectNext => self.select(position.map_or(0, |p| p + 1)),
+            Action::SelectPrevious => self.select(position.map_or(0, |p| p.saturating_sub(1))),
+            Action::NextTopLevel => {
+                let start = position.map_or(0, |p| p + 1);
+                if let Some(offset) = self.nav_order[start..].iter().position(|e| e.depth == 1) {
+                    self.select(start + offset);
+                }
+            }
+            Action::OpenLink => {
+                if let Some(entry) = position.map(|p| self.nav_order[p].clone()) {
+                    match &entry.link {
+                        Some(url) => ctx.output().open_url(url),
+                        None => self.history.push(entry.id),
+                    }
+                    if entry.depth == 0 {
+                        self.mark_read(entry.id);
+                    }
+                }
+            }
+            Action::OpenThread => {
+                if let Some(p) = position {
+                    let entry = &self.nav_order[p];
+                    let id = entry.id;
+                    // Opening a story from the list replaces the thread beside it.
+                    if self.split && entry.depth == 0 && self.history.first() != Some(&id) {
+                        self.show_thread(id);
+                    } else {
+                        self.history.push(id);
+                    }
+                }
+            }
+            Action::NextNew => {
+                let start = position.map_or(0, |p| p + 1);
+                if let Some(offset) = self.nav_order[start..].iter().position(|e| e.new) {
+                    self.select(start + offset);
+                }
+            }
+            Action::ToggleCollapse => self.toggle_request = self.selected,
+            Action::Back => {
+                self.history.pop();
+            }
+            Action::ShowTop => self.show_tab(Tab::Top),
+            Action::ShowNew => self.show_tab(Tab::New),
+            Action::ShowShow => self.show_tab(Tab::Show),
+            Action::ShowFavorites => self.show_tab(Tab::Favorites),
+            Action::ShowSaved => self.show_tab(Tab::Saved),
+            Action::ToggleHelp => self.show_help = !self.show_help,
+            Action::CommandPalette => self.palette.toggle(),
+            Action::ToggleTheme => {
+                self.settings.theme = self.theme().opposite();
+                self.apply_settings(ctx);
+                self.save_settings();
+            }
+            Action::Refresh => self.refresh.notify_all(),
+            Action::ShowSettings => self.show_settings = true,
+            Action::ShowAlerts => self.show_alerts = !self.show_alerts,
+            Action::ZoomIn | Action::ZoomOut | Action::ResetZoom => {
+                match action {
+                    Action::ZoomIn => self.settings.zoom(0.1),
+                    Action::ZoomOut => self.settings.zoom(-0.1),
+                    _ => self.settings.ui_scale = 1.0,
+                }
+                self.apply_settings(ctx);
+                self.save_settings();
+            }
+            Action::FocusSearch => {
+                if !self.split {
+                    self.switch_thread(None);
+                }
+                self.focus_search = true;
+            }
+            Action::Bookmark => {
+                // With nothing selected, the item the open thread is about.
+                let id = match self.selected.or_else(|| self.history.last().copied()) {
+                    Some(id) => id,
+                    None => return,
+                };
+                let bookmark = {
+                    let data = self.data.lock().unwrap();
+                    match data.comments.get(&id) {
+                        Some(CommentState::Loaded(c)) => Some(c.bookmark()),
+                        _ => data.story(id).map(|s| s.bookmark()),
+                    }
+                };
+                if let Some(bookmark) = bookmark {
+                    self.bookmarks.toggle(bookmark);
+                    self.save_bookmarks();
+                }
+            }
+            Action::ExportThread => {
+                // The selected story, or else the one whose thread is open.
+                let story = {
+                    let data = self.data.lock().unwrap();
+                    self.selected
+                        .and_then(|id| data.story(id))
+                        .or_else(|| self.history.first().and_then(|id| data.story(*id)))
+                };
+                if let Some(story) = story.filter(|s| !self.exports.contains_key(&s.id)) {
+                    self.start_export(story, self.export_format);
+                }
+            }
+        }
+    }
+
+    fn select(&mut self, index: usize) {
+        if let Some(entry) = self.nav_order.get(index) {
+            self.selected = Some(entry.id);
+            self.scroll_to_selected = true;
+        }
+    }
+
+    /// The chosen theme, or the default if it has gone from the themes file.
+    fn theme(&self) -> &Theme {
+        self.themes
+            .iter()
+            .find(|t| t.name == self.settings.theme)
+            .unwrap_or(&self.themes[0])
+    }
+
+    /// Reads the themes file, keeping the built-in themes if it can't be read.
+    fn load_themes(&mut self) {
+        self.themes_error = None;
+        let user = match config::load(theme::CONFIG_FILE) {
+            Ok(user) => user.unwrap_or_default(),
+            Err(e) => {
+                self.themes_error = Some(format!("Couldn't read themes: {}", e));
+                UserThemes::default()
+            }
+        };
+        self.themes = theme::all(user);
+    }
+
+    fn links_in_app(&self) -> bool {
+        self.settings.links == LinkBehavior::InApp
+    }
+
+    /// Pushes whatever changed since the settings were last applied to the context and the
+    /// feed threads.
+    fn apply_settings(&mut self, ctx: &egui::CtxRef) {
+        let previous = self.applied.replace(self.settings.clone());
+        let settings = &self.settings;
+        if previous
+            .as_ref()
+            .map_or(true, |p| p.theme != settings.theme)
+        {
+            ctx.set_visuals(self.theme().visuals());
+        }
+        if previous
+            .as_ref()
+            .map_or(true, |p| p.ui_scale != settings.ui_scale)
+        {
+            ctx.set_pixels_per_point(self.native_pixels_per_point * settings.ui_scale);
+        }
+        if previous.as_ref().map_or(true, |p| !p.same_fonts(settings)) {
+            let (fonts, errors) = settings.fonts();
+            ctx.set_fonts(fonts);
+            self.fonts_error = (!errors.is_empty()).then(|| errors.join("\n"));
+        }
+
+        let mut data = self.data.lock().unwrap();
+        let feeds_changed = data.refresh_seconds != self.settings.refresh_seconds
+            || data.page_size != self.settings.page_size;
+        data.refresh_seconds = self.settings.refresh_seconds;
+        data.page_size = self.settings.page_size;
+        if feeds_changed {
+            // Wake the feed threads so they pick up the new interval or page size now.
+            self.refresh.notify_all();
+        }
+    }
+
+    fn save_settings(&mut self) {
+        self.settings_error = config::save(settings::CONFIG_FILE, &self.settings)
+            .err()
+            .map(|e| format!("Couldn't save settings: {}", e));
+    }
+
+    fn show_tab(&mut self, tab: Tab) {
+        self.tab = tab;
+        // Beside the list, the open thread can stay.
+        if !self.split {
+            self.switch_thread(None);
+        }
+    }
+
+    /// Shows thread tab `index`, or the feed for `None`.
+    fn switch_thread(&mut self, index: Option<usize>) {
+        if let Some(active) = self.active_thread {
+            self.thread_tabs[active].history = self.history.clone();
+        }
+        self.active_thread = index;
+        self.history = index.map_or_else(Vec::new, |i| self.thread_tabs[i].history.clone());
+    }
+
+    fn thread_tab(&self, id: u32) -> Option<usize> {
+        self.thread_tabs
+            .iter()
+            .position(|t| t.history.first() == Some(&id))
+    }
+
+    /// Shows `id`'s thread: its tab if it has one, otherwise in place of the active thread.
+    fn show_thread(&mut self, id: u32) {
+        match self.thread_tab(id) {
+            Some(index) => self.switch_thread(Some(index)),
+            None => self.history = vec![id],
+        }
+    }
+
+    /// Opens `id` in a new tab without switching to it.
+    fn open_in_background(&mut self, id: u32) {
+        if self.thread_tab(id).is_none() {
+            self.thread_tabs.push(ThreadTab {
+                history: vec![id],
+                title: None,
+            });
+        }
+    }
+
+    /// Closes thread tab `index`. Closing the active tab shows its neighbor, if there is one.
+    fn close_thread(&mut self, index: usize) {
+        let active = self.active_thread;
+        self.switch_thread(None);
+        self.thread_tabs.remove(index);
+        let next = match active {
+            Some(active) if active == index => {
+                (!self.thread_tabs.is_empty()).then(|| index.min(self.thread_tabs.len() - 1))
+            }
+            Some(active) if active > index => Some(active - 1),
+            active => active,
+        };
+        self.switch_thread(next);
+    }
+
+    /// Brings the thread tabs in line with whatever happened to `history` this frame: an item
+    /// opened from a feed gets a tab, and a tab whose history has been emptied is closed.
+    fn sync_thread_tabs(&mut self) {
+        match self.active_thread {
+            Some(active) if self.history.is_empty() => {
+                self.thread_tabs.remove(active);
+                self.active_thread = None;
+            }
+            Some(active) => self.thread_tabs[active].history = self.history.clone(),
+            None if !self.history.is_empty() => {
+                self.thread_tabs.push(ThreadTab {
+                    history: self.history.clone(),
+                    title: None,
+                });
+                self.active_thread = Some(self.thread_tabs.len() - 1);
+            }
+            None => {}
+        }
+
+        let data = self.data.lock().unwrap();
+        for tab in self.thread_tabs.iter_mut().filter(|t| t.title.is_none()) {
+            let root = match tab.history.first() {
+                Some(root) => root,
+                None => continue,
+            };
+            tab.title = match (data.items.get(root), data.comments.get(root)) {
+                (Some(ItemState::Loaded(s)), _) => s.title.clone(),
+                (_, Some(CommentState::Loaded(c))) => Some(format!(
+                    "Comment by {}",
+                    c.by.as_deref().unwrap_or("[unknown]")
+                )),
+                _ => None,
+            };
+        }
+    }
+
+    /// A frame to wrap a selectable item in, highlighted while it is selected.
+    fn selectable_frame(&self, ui: &egui::Ui, id: u32) -> egui::Frame {
+        let fill = if self.selected == Some(id) {
+            ui.visuals().hyperlink_color.linear_multiply(0.15)
+        } else {
+            egui::Color32::TRANSPARENT
+        };
+        egui::Frame::none().fill(fill)
+    }
+
+    /// Records an item's place in the keyboard navigation order for this frame.
+    fn track_selectable(&mut self, response: &egui::Response, entry: NavEntry) {
+        if self.scroll_to_selected && self.selected == Some(entry.id) {
+            response.scroll_to_me(egui::Align::Center);
+            self.scroll_to_selected = false;
+        }
+        self.nav_order.push(entry);
+    }
+
+    /// Forces a collapsing header open or shut if it was toggled from the keyboard, or its
+    /// subtree was collapsed or expanded.
+    fn take_toggle(&mut self, id: u32) -> Option<bool> {
+        if self.toggle_request == Some(id) {
+            self.toggle_request = None;
+            Some(!self.expanded.contains(&id))
+        } else {
+            self.header_requests.remove(&id)
+        }
+    }
+
+    /// Opens or shuts the replies of every loaded comment in `ids` and below. Those not drawn
+    /// yet follow suit when they are.
+    fn set_subtree_open(&mut self, ids: &[u32], open: bool) {
+        let comments = self.data.lock().unwrap().timeline(ids);
+        let now = self.clock.now();
+        for c in comments
+            .iter()
+            .filter(|c| c.kids.as_ref().map_or(false, |k| !k.is_empty()))
+        {
+            self.header_requests.insert(c.id, open);
+            self.open_state.insert(c.id, (open, now));
+        }
+    }
+
+    /// Whether a header starts out open: as the user last left it, or else `default`.
+    fn initially_open(&self, id: u32, default: bool) -> bool {
+        self.open_state.get(&id).map_or(default, |(open, _)| *open)
+    }
+
+    /// Notes a header's state after drawing it, remembering it if the user just changed it,
+    /// whether by clicking or with `toggle` from the keyboard.
+    fn track_header<R>(
+        &mut self,
+        id: u32,
+        header: &egui::CollapsingResponse<R>,
+        toggle: Option<bool>,
+    ) {
+        let was_open = self.expanded.contains(&id);
+        if let Some(open) = toggle.or_else(|| header.header_response.clicked().then(|| !was_open)) {
+            self.open_state.insert(id, (open, self.clock.now()));
+        }
+        self.set_expanded(id, header.body_returned.is_some());
+    }
+
+    /// A vertical scroll area for `key`, put back where it was left whenever it comes into view.
+    fn scroll_area(&mut self, ui: &egui::Ui, key: ScrollKey) -> egui::ScrollArea {
+        let (last_frame, this_frame) = &mut self.shown_views;
+        if !last_frame.contains(&key) && self.scroll_offsets.contains_key(&key) {
+            self.scroll_restores.insert(key, ui.input().time);
+        }
+        this_frame.push(key);
+
+        let area = egui::containers::ScrollArea::vertical()
+            .id_source(key)
+            .auto_shrink([false, false]);
+        match self.scroll_offsets.get(&key) {
+            Some((offset, _)) if self.scroll_restores.contains_key(&key) => {
+                area.vertical_scroll_offset(*offset)
+            }
+            _ => area,
+        }
+    }
+
+    /// Called at the end of a scroll area's contents to remember how far down it is.
+    fn track_scroll(&mut self, ui: &egui::Ui, key: ScrollKey) {
+        let margin = ui.visuals().clip_rect_margin;
+        let offset = ui.clip_rect().top() - ui.min_rect().top() + margin;
+        let max_offset = ui.min_rect().height() - ui.clip_rect().height() + 2.0 * margin;
+        if let Some(started) = self.scroll_restores.get(&key) {
+            let target = self
+                .scroll_offsets
+                .get(&key)
+                .map_or(0., |(offset, _)| *offset);
+            let input = ui.input();
+            let interrupted = input.scroll_delta.y != 0.
+                || input.pointer.any_pressed()
+                || self.scroll_to_selected;
+            if max_offset >= target || interrupted || input.time - started > SCROLL_RESTORE_SECS {
+                self.scroll_restores.remove(&key);
+            }
+            if !interrupted {
+                return;
+            }
+        }
+        self.scroll_offsets
+            .insert(key, (offset.max(0.), self.clock.now()));
+    }
+
+    fn set_expanded(&mut self, id: u32, open: bool) {
+        if open {
+            self.expanded.insert(id);
+        } else {
+            self.expanded.remove(&id);
+        }
+    }
+
+    fn render_stories(&mut self, ui: &mut egui::Ui) {
+        let query = self.filter.trim().to_string();
+        if self.tab == Tab::Favorites {
+            self.render_import_bar(ui);
+        }
+
+        let current = self.data.lock().unwrap();
+        // A filter searches every feed that has been loaded, not just the current tab.
+        let feeds = if query.is_empty() {
+            vec![match self.tab {
+                Tab::Top => feed_stories(&current.top),
+                Tab::New => feed_stories(&current.new),
+                Tab::Show => feed_stories(&current.show),
+                Tab::Favorites => current.imported_stories(self.imported_list),
+                Tab::Saved => unreachable!("saved items are rendered by render_saved"),
+            }]
+        } else {
+            vec![
+                feed_stories(&current.top),
+                feed_stories(&current.new),
+                feed_stories(&current.show),
+                current.imported_stories(self.imported_list),
+            ]
+        };
+        std::mem::drop(current);
+
+        let mut seen = HashSet::new();
+        let stories: Vec<(usize, LocalStory)> = feeds
+            .into_iter()
+            .flatten()
+            .filter(|(_, s)| s.matches(&query) && seen.insert(s.id))
+            .collect();
+
+        let mut stories = stories;
+        let before = stories.len();
+        if !self.show_muted {
+            stories.retain(|(_, s)| {
+                !self
+                    .mutes
+                    .mutes_story(s.title.as_deref(), s.domain().as_deref(), s.by.as_deref())
+            });
+        }
+        let hidden = before - stories.len();
+        // Threads stay put while they're open, even though opening one marks it read.
+        if self.hide_read {
+            stories.retain(|(_, s)| {
+                !self.read.contains_key(&s.id)
+                    || self.expanded.contains(&s.id)
+                    || self.history.first() == Some(&s.id)
+            });
+        }
+
+        ui.horizontal(|ui| {
+            let search = ui.add(
+                egui::TextEdit::singleline(&mut self.filter)
+                    .hint_text("Filter by title, site or author"),
+            );
+            if self.focus_search {
+                search.request_focus();
+                self.focus_search = false;
+            }
+            if !self.filter.is_empty() {
+                if ui.small_button("✖").on_hover_text("Clear filter").clicked() {
+                    self.filter.clear();
+                }
+                ui.label(format!(
+                    "{} matching stor{} across all feeds",
+                    stories.len(),
+                    if stories.len() != 1 { "ies" } else { "y" }
+                ));
+            }
+            self.muted_toggle(ui, hidden);
+            ui.with_layout(egui::Layout::right_to_left(), |ui| {
+                ui.checkbox(&mut self.hide_read, "Hide read");
+            });
+        });
+        ui.separator();
+
+        let view = ScrollKey::Feed(self.tab);
+        self.scroll_area(ui, view).show(ui, |ui| {
+            stories.iter().for_each(|(_, s)| {
+                if s.title.is_some() {
+                    if let Some(by) = &s.by {
+                        let read = self.read.contains_key(&s.id);
+                        let header = self.selectable_frame(ui, s.id).show(ui, |ui| {
+                            if read {
+                                ui.visuals_mut().override_text_color =
+                                    Some(ui.visuals().weak_text_color());
+                            }
+                            self.render_story_header(ui, s, &query)
+                        });
+                        if header.inner {
+                            self.mark_read(s.id);
+                        }
+                        let header = header.response;
+                        self.track_selectable(
+                            &header,
+                            NavEntry {
+                                id: s.id,
+                                depth: 0,
+                                link: s.url.clone(),
+                                new: false,
+                            },
+                        );
+
+                        let kids = s.kids.as_deref().unwrap_or_default();
+                        let comment_count = s.descendants.map(|d| d as usize).unwrap_or(kids.len());
+                        let (_, loaded) = self.data.lock().unwrap().thread_size(kids);
+
+                        let mut header = format!(
+                            "{} Comment{}",
+                            comment_count,
+                            if comment_count != 1 { "s" } else { "" }
+                        );
+                        if loaded > 0 && loaded < comment_count {
+                            header.push_str(&format!(" ({}/{} loaded)", loaded, comment_count));
+                        }
+                        let new = self.visits.get(&s.id).map_or(0, |visit| {
+                            let mut data = self.data.lock().unwrap();
+                            let times = data.comment_times(s.id, kids);
+                            visit.new_count(times, self.open_stories.contains(&s.id))
+                        });
+                        if new > 0 {
+                            header.push_str(&format!(", {} new", new));
+                        }
+
+                        if self.split {
+                            // The thread opens in the other pane rather than inline.
+                            let showing = self.history.first() == Some(&s.id);
+                            let toggled = self.take_toggle(s.id).is_some();
+                            let label = egui::SelectableLabel::new(showing, header);
+                            let label = ui
+                                .add_enabled(!kids.is_empty(), label)
+                                .on_hover_text("Middle-click to open in a new tab");
+                            if label.clicked() || toggled {
+                                self.show_thread(s.id);
+                            } else if label.middle_clicked() {
+                                self.open_in_background(s.id);
+                            }
+                            ui.separator();
+                            return;
+                        }
+
+                        let toggle = self.take_toggle(s.id);
+                        let thread = egui::containers::CollapsingHeader::new(header)
+                            .enabled(!kids.is_empty())
+                            .default_open(self.initially_open(s.id, false))
+                            .id_source(s.id)
+                            .open(toggle)
+                            .show(ui, |ui| {
+                                self.export_menu(ui, s);
+                                self.order_picker(ui, s.id);
+                                if new > 0 && ui.small_button("Jump to next new comment").clicked()
+                                {
+                                    self.selected = Some(s.id);
+                                    self.queued_action = Some(Action::NextNew);
+                                }
+                                if let Some(prefetch) = self.prefetches.get(&s.id) {
+                                    if !prefetch.is_done() {
+                                        let total = comment_count.max(loaded).max(1);
+                                        ui.add(
+                                            egui::ProgressBar::new(loaded as f32 / total as f32)
+                                                .text(format!(
+                                                    "{}/{} comments loaded",
+                                                    loaded, total
+                                                )),
+                                        );
+                                        ui.ctx().request_repaint();
+                                    }
+                                }
+                                self.render_thread(ui, kids, by, s.id);
+                            });
+                        if thread.header_response.middle_clicked() {
+                            self.open_in_background(s.id);
+                        }
+                        self.track_header(s.id, &thread, toggle);
+                        if thread.body_returned.is_some() {
+                            self.mark_read(s.id);
+                            self.open_threads.push((s.id, kids.to_vec()));
+                        }
+
+                        ui.separator();
+                    }
+                }
+            });
+
+            self.track_scroll(ui, view);
+        });
+    }
+
+    /// Brings visits and prefetches in line with the threads drawn this frame.
+    fn sync_open_threads(&mut self) {
+        let open_threads = std::mem::take(&mut self.open_threads);
+        self.timelines
+            .retain(|story, _| open_threads.iter().any(|(id, _)| id == story));
+        let open_stories: HashSet<u32> = open_threads.iter().map(|(id, _)| *id).collect();
+        // Requests for replies that were never drawn would otherwise wait forever, and the
+        // outline lines are only needed while their thread is open.
+        if self.open_stories.difference(&open_stories).next().is_some() {
+            self.header_requests.clear();
+            self.outline_lines.clear();
+        }
+        self.sync_visits(open_stories);
+        self.sync_prefetches(open_threads);
+    }
+
+    /// Starts a new visit for every story whose comments weren't on screen last frame.
+    fn sync_visits(&mut self, open_stories: HashSet<u32>) {
+        let now = self.clock.now();
+        for id in open_stories.difference(&self.open_stories) {
+            match self.visits.get_mut(id) {
+                Some(visit) => visit.begin(now),
+                None => {
+                    self.visits.insert(
+                        *id,
+                        Visit {
+                            started: now,
+                            previous: None,
+                        },
+                    );
+                }
+            }
+        }
+        self.open_stories = open_stories;
+    }
+
+    /// Starts prefetching threads that were just opened, and cancels those the user has left.
+    /// A timeline needs the whole thread, so those are prefetched whatever the setting.
+    fn sync_prefetches(&mut self, open_threads: Vec<(u32, Vec<u32>)>) {
+        let setting = self.settings.prefetch_threads;
+        let orders = &self.comment_orders;
+        let enabled = |id: &u32| setting || orders.get(id) == Some(&CommentOrder::Timeline);
+        self.prefetches.retain(|id, prefetch| {
+            let keep = enabled(id)
+                && open_threads
+                    .iter()
+                    .any(|(open, kids)| open == id && !kids.is_empty());
+            if !keep {
+                prefetch.cancel();
+            }
+            keep
+        });
+
+        for (id, kids) in open_threads {
+            if enabled(&id) && !kids.is_empty() && !self.prefetches.contains_key(&id) {
+                let prefetch = Prefetch::start(&self.data, &kids);
+                self.prefetches.insert(id, prefetch);
+            }
+        }
+    }
+
+    /// Draws a story's title line and byline, highlighting any matches of `query`.
+    /// Returns whether the story's link was clicked.
+    fn render_story_header(&mut self, ui: &mut egui::Ui, s: &LocalStory, query: &str) -> bool {
+        let mut opened = false;
+        let highlight = ui.visuals().hyperlink_color.linear_multiply(0.35);
+        let mark = |text: String| {
+            let mut text = egui::RichText::new(text);
+            if !find_matches(text.text(), query).is_empty() {
+                text = text.background_color(highlight);
+            }
+            text
+        };
+
+        ui.horizontal_wrapped(|ui| {
+            let title = s.title.as_deref().unwrap_or_default();
+            if let Some(term) = watch::matching_term(&self.watch.terms, title) {
+                ui.label(watch::badge(term));
+            }
+            // Read stories are dimmed by overriding the text color, which `strong` would ignore.
+            let color = ui
+                .visuals()
+                .override_text_color
+                .unwrap_or_else(|| ui.visuals().strong_text_color());
+            if query.is_empty() {
+                ui.label(egui::RichText::new(title).color(color));
+            } else {
+                ui.label(highlighted(title, query, color, highlight));
+            }
+            if let Some(url) = &s.url {
+                if let Ok(u) = Url::parse(url) {
+                    ui.horizontal(|ui| {
+                        ui.spacing_mut().item_spacing.x = 0.0;
+                        ui.label("(");
+                        opened = ui
+                            .hyperlink_to(
+                                mark(s.domain().unwrap_or_else(|| url.clone())),
+                                u.to_string(),
+                            )
+                            .clicked();
+                        ui.label(")");
+                    });
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.spacing_mut().item_spacing.x = 2.5;
+            if let Some(score) = s.score {
+                ui.label(format!("{} points", score));
+                ui.add(egui::widgets::Separator::default().vertical());
+            }
+            if let Some(by) = &s.by {
+                ui.hyperlink_to(mark(by.clone()), format!("{}/user?id={}", BASE_URL, by));
+                ui.add(egui::widgets::Separator::default().vertical());
+            }
+
+            self.clock.label(ui, s.time);
+
+            ui.hyperlink_to("↗", format!("{}/item?id={}", BASE_URL, s.id));
+            self.bookmark_button(ui, s.id, || s.bookmark());
+        });
+
+        opened
+    }
+
+    /// Reads the imported lists saved by a previous import and loads their stories.
+    fn load_imported(&self) {
+        let imported: Imported = match config::load(favorites::DATA_FILE) {
+            Ok(Some(imported)) => imported,
+            Ok(None) => return,
+            Err(e) => {
+                let status = format!("Couldn't read imported lists: {}", e);
+                self.data.lock().unwrap().import_status = Some(status);
+                return;
+            }
+        };
+
+        let ids: Vec<u32> = imported
+            .favorites
+            .iter()
+            .chain(&imported.upvoted)
+            .copied()
+            .collect();
+        {
+            let mut data = self.data.lock().unwrap();
+            data.favorite_ids = imported.favorites;
+            data.upvoted_ids = imported.upvoted;
+        }
+        let data = Arc::clone(&self.data);
+        thread::spawn(move || fetch_imported(&JsonClient::new(), &data, &ids));
+    }
+
+    /// Scrapes `list` for stories we don't have yet, or all of it again if `full`, then saves
+    /// the ids and loads the new stories.
+    fn import(&self, list: List, full: bool) {
+        let username = self.import_username.trim().to_string();
+        let password = self.auth.as_ref().map(|a| a.password.clone());
+        let base_url = self.settings.import_base_url.clone();
+        let data = Arc::clone(&self.data);
+        {
+            let mut data = data.lock().unwrap();
+            data.importing = true;
+            data.import_status = Some(format!("Importing {}…", list.name().to_lowercase()));
+        }
+
+        thread::spawn(move || {
+            let result = (|| -> Result<Vec<u32>, Box<dyn std::error::Error>> {
+                let importer = Importer::new(&base_url)?;
+                let cookie = match (list, password) {
+                    (List::Upvoted, Some(password)) => Some(importer.login(&username, &password)?),
+                    (List::Upvoted, None) => return Err("sign in to import upvoted stories".into()),
+                    (List::Favorites, _) => None,
+                };
+                let known: HashSet<u32> = if full {
+                    HashSet::new()
+                } else {
+                    data.lock()
+                        .unwrap()
+                        .imported_ids(list)
+                        .iter()
+                        .copied()
+                        .collect()
+                };
+                importer.fetch_new(list, &username, cookie.as_deref(), &known)
+            })();
+
+            let new_ids = {
+                let mut data = data.lock().unwrap();
+                data.importing = false;
+                match result {
+                    Ok(new_ids) => {
+                        let ids = data.imported_ids(list);
+                        if full {
+                            *ids = new_ids.clone();
+                        } else {
+                            ids.splice(0..0, new_ids.iter().copied());
+                        }
+                        let imported = Imported {
+                            favorites: data.favorite_ids.clone(),
+                            upvoted: data.upvoted_ids.clone(),
+                        };
+                        data.import_status =
+                            Some(match config::save(favorites::DATA_FILE, &imported) {
+                                Ok(()) => format!("{} new", new_ids.len()),
+                                Err(e) => format!("Imported, but couldn't save: {}", e),
+                            });
+                        new_ids
+                    }
+                    Err(e) => {
+                        data.import_status = Some(format!("Import failed: {}", e));
+                        return;
+                    }
+                }
+            };
+            fetch_imported(&JsonClient::new(), &data, &new_ids);
+        });
+    }
+
+    fn render_import_bar(&mut self, ui: &mut egui::Ui) {
+        if self.import_username.is_empty() {
+            if let Some(auth) = &self.auth {
+                self.import_username = auth.username.clone();
+            }
+        }
+        let (importing, status) = {
+            let data = self.data.lock().unwrap();
+            (data.importing, data.import_status.clone())
+        };
+
+        ui.horizontal_wrapped(|ui| {
+            ui.selectable_value(&mut self.imported_list, List::Favorites, "Favorites");
+            ui.selectable_value(&mut self.imported_list, List::Upvoted, "Upvoted");
+            ui.add(egui::widgets::Separator::default().vertical());
+
+            ui.add(
+                egui::TextEdit::singleline(&mut self.import_username)
+                    .hint_text("Username")
+                    .desired_width(100.),
+            );
+            let ready = !importing && !self.import_username.trim().is_empty();
+            if ui
+                .add_enabled(ready, egui::Button::new("Import new"))
+                .on_hover_text("Fetch pages until reaching stories already imported")
+                .clicked()
+            {
+                self.import(self.imported_list, false);
+            }
+            if ui
+                .add_enabled(ready, egui::Button::new("Re-import all"))
+                .clicked()
+            {
+                self.import(self.imported_list, true);
+            }
+            if importing {
+                ui.ctx().request_repaint();
+            }
+            if let Some(status) = status {
+                ui.label(status);
+            }
+        });
+        ui.separator();
+    }
+
+    /// An "Export" menu for a story's thread, with the progress or outcome of its export.
+    fn export_menu(&mut self, ui: &mut egui::Ui, s: &LocalStory) {
+        ui.horizontal(|ui| {
+            let exporting = self.exports.contains_key(&s.id);
+            let mut chosen = None;
+            ui.add_enabled_ui(!exporting, |ui| {
+                ui.menu_button("Export", |ui| {
+                    for format in Format::ALL {
+                        if ui.button(format.name()).clicked() {
+                            chosen = Some(format);
+                            ui.close_menu();
+                        }
+                    }
+                });
+            });
+            if let Some(format) = chosen {
+                self.export_format = format;
+                self.start_export(s.clone(), format);
+            }
+
+            if exporting {
+                let kids = s.kids.as_deref().unwrap_or_default();
+                let (known, loaded) = self.data.lock().unwrap().thread_size(kids);
+                ui.add(
+                    egui::ProgressBar::new(loaded as f32 / known.max(1) as f32)
+                        .desired_width(160.)
+                        .text(format!("Loading {}/{} comments", loaded, known)),
+                );
+            } else if let Some((_, status)) =
+                self.export_status.as_ref().filter(|(id, _)| *id == s.id)
+            {
+                ui.small(status.as_str());
+            }
+        });
+    }
+
+    /// Fetches the rest of a story's thread, to be written out once it has all arrived.
+    fn start_export(&mut self, story: LocalStory, format: Format) {
+        let prefetch = Prefetch::start(&self.data, story.kids.as_deref().unwrap_or_default());
+        self.exports.insert(
+            story.id,
+            PendingExport {
+                story,
+                format,
+                prefetch,
+            },
+        );
+    }
+
+    /// Writes out the exports whose threads have finished loading.
+    fn finish_exports(&mut self) {
+        let done: Vec<u32> = self
+            .exports
+            .iter()
+            .filter(|(_, export)| export.prefetch.is_done())
+            .map(|(id, _)| *id)
+            .collect();
+        for id in done {
+            let PendingExport { story, format, .. } = match self.exports.remove(&id) {
+                Some(export) => export,
+                None => continue,
+            };
+            let thread = self.export_thread(&story);
+            let total = story.descendants.map(|d| d as usize).unwrap_or_default();
+            let exported = thread.comment_count();
+            let status = match thread.save(format) {
+                Ok(path) if exported < total => format!(
+                    "Saved {} of {} comments to {}; the rest couldn't be fetched",
+                    exported,
+                    total,
+                    path.display()
+                ),
+                Ok(path) => format!("Saved to {}", path.display()),
+                Err(e) => format!("Couldn't export: {}", e),
+            };
+            self.export_status = Some((id, status));
+        }
+    }
+
+    /// Copies a story and its comment tree, as far as it has loaded, into an exportable thread.
+    fn export_thread(&self, s: &LocalStory) -> export::Thread {
+        fn comments(data: &Data, kids: &[u32]) -> Vec<export::Comment> {
+            kids.iter()
+                .filter_map(|id| match data.comments.get(id) {
+                    Some(CommentState::Loaded(c)) => Some(export::Comment {
+                        id: c.id,
+                        by: c.by.clone(),
+                        time: c.time,
+                        text: c.text.clone(),
+                        deleted: c.deleted,
+                        dead: c.dead,
+                        replies: comments(data, c.kids.as_deref().unwrap_or_default()),
+                    }),
+                    _ => None,
+                })
+                .collect()
+        }
+
+        let data = self.data.lock().unwrap();
+        export::Thread {
+            id: s.id,
+            title: s.title.clone(),
+            url: s.url.clone(),
+            by: s.by.clone(),
+            time: s.time,
+            score: s.score,
+            text: s.text.clone(),
+            comments: comments(&data, s.kids.as_deref().unwrap_or_default()),
+        }
+    }
+
+    /// A "save" toggle for a story or comment. `bookmark` snapshots the item when saving it.
+    /// `bookmark` is only called when the button is clicked, since it copies the item's text.
+    fn bookmark_button(&mut self, ui: &mut egui::Ui, id: u32, bookmark: impl FnOnce() -> Bookmark) {
+        let saved = self.bookmarks.contains(id);
+        if ui
+            .small_button(if saved { "saved" } else { "save" })
+            .on_hover_text(if saved {
+                "Remove from Saved"
+            } else {
+                "Add to Saved"
+            })
+            .clicked()
+        {
+            self.bookmarks.toggle(bookmark());
+            self.save_bookmarks();
+        }
+    }
+
+    fn load_session(&mut self) {
+        let session: Session = match config::load(SESSION_FILE) {
+            Ok(session) => session.unwrap_or_default(),
+            Err(e) => {
+                self.session_error = Some(format!("Couldn't read session: {}", e));
+                return;
+            }
+        };
+        self.read = session.read;
+        self.visits = session.visits;
+        self.open_state = session.open_state;
+        self.scroll_offsets = session.scroll_offsets;
+        self.comment_orders = session.comment_orders;
+        self.thread_tabs = session.thread_tabs;
+        self.active_thread = session.active_thread;
+        let mut data = self.data.lock().unwrap();
+        data.alerted = session.alerted;
+        data.alerts = session.alerts;
+    }
+
+    fn save_session(&mut self) {
+        let session = {
+            let data = self.data.lock().unwrap();
+            Session {
+                active_thread: self.active_thread,
+                alerted: data.alerted.clone(),
+                alerts: data.alerts.clone(),
+                read: self.read.clone(),
+                visits: self.visits.clone(),
+                open_state: self.open_state.clone(),
+                scroll_offsets: self.scroll_offsets.clone(),
+                comment_orders: self.comment_orders.clone(),
+                thread_tabs: self.thread_tabs.clone(),
+            }
+        };
+        self.session_error = config::save(SESSION_FILE, &session)
+            .err()
+            .map(|e| format!("Couldn't save session: {}", e));
+    }
+
+    fn save_bookmarks(&mut self) {
+        self.bookmarks_error = config::save(bookmarks::DATA_FILE, &self.bookmarks)
+            .err()
+            .map(|e| format!("Couldn't save bookmarks: {}", e));
+    }
+
+    fn render_saved(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Sort:");
+            ui.selectable_value(&mut self.saved_oldest_first, false, "Newest first");
+            ui.selectable_value(&mut self.saved_oldest_first, true, "Oldest first");
+            ui.add(egui::widgets::Separator::default().vertical());
+
+            ui.label("Tag:");
+            ui.selectable_value(&mut self.saved_tag, None, "All");
+            for tag in self.bookmarks.tags() {
+                ui.selectable_value(&mut self.saved_tag, Some(tag.clone()), tag);
+            }
+        });
+        if let Some(error) = &self.bookmarks_error {
+            ui.colored_label(egui::Color32::RED, error.as_str());
+        }
+        ui.separator();
+
+        let bookmarks = self
+            .bookmarks
+            .sorted(self.saved_tag.as_deref(), self.saved_oldest_first);
+        self.scroll_area(ui, ScrollKey::Feed(Tab::Saved))
+            .show(ui, |ui| {
+                if bookmarks.is_empty() {
+                    ui.label(
+                        egui::RichText::new(
+                            "Nothing saved yet. Use \"save\" on a story or comment.",
+                        )
+                        .weak(),
+                    );
+                }
+
+                let mut changed = false;
+                for b in &bookmarks {
+                    let frame = self.selectable_frame(ui, b.id).show(ui, |ui| {
+                        ui.horizontal_wrapped(|ui| {
+                            match &b.title {
+                                Some(title) => ui.label(egui::RichText::new(title).strong()),
+                                None => ui.label(egui::RichText::new("Comment").weak()),
+                            };
+                            if let Some(url) = &b.url {
+                                ui.hyperlink_to("↗", url);
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.spacing_mut().item_spacing.x = 2.5;
+                            if let Some(by) = &b.by {
+                                ui.hyperlink_to(by, format!("{}/user?id={}", BASE_URL, by));
+                                ui.add(egui::widgets::Separator::default().vertical());
+                            }
+                            self.clock.label(ui, b.time);
+                            ui.add(egui::widgets::Separator::default().vertical());
+                            ui.label("saved");
+                            self.clock.label(ui, b.saved);
+                        });
+                        if let Some(text) = &b.text {
+                            if let Some(id) = render_html(ui, text, self.links_in_app()) {
+                                self.history.push(id);
+                            }
+                        }
+
+                        ui.horizontal_wrapped(|ui| {
+                            let open = ui.small_button("open");
+                            if open.clicked() {
+                                self.history.push(b.id);
+                            } else if open.middle_clicked() {
+                                self.open_in_background(b.id);
+                            }
+                            if ui.small_button("remove").clicked() {
+                                self.bookmarks.remove(b.id);
+                                changed = true;
+                            }
+                            ui.add(egui::widgets::Separator::default().vertical());
+
+                            for tag in &b.tags {
+                                if ui
+                                    .small_button(tag)
+                                    .on_hover_text("Click to remove tag")
+                                    .clicked()
+                                {
+                                    if let Some(b) = self.bookmarks.get_mut(b.id) {
+                                        b.tags.retain(|t| t != tag);
+                                    }
+                                    changed = true;
+                                }
+                            }
+                            let draft = self.tag_drafts.entry(b.id).or_default();
+                            let input = ui.add(
+                                egui::TextEdit::singleline(draft)
+                                    .hint_text("Add tag")
+                                    .desired_width(80.),
+                            );
+                            if input.lost_focus()
+                                && ui.input().key_pressed(egui::Key::Enter)
+                                && !draft.trim().is_empty()
+                            {
+                                let tag = draft.trim().to_string();
+                                draft.clear();
+                                if let Some(b) = self.bookmarks.get_mut(b.id) {
+                                    if !b.tags.contains(&tag) {
+                                        b.tags.push(tag);
+                                        changed = true;
+                                    }
+                                }
+                            }
+                        });
+                    });
+                    self.track_selectable(
+                        &frame.response,
+                        NavEntry {
+                            id: b.id,
+                            depth: 0,
+                            link: b.url.clone(),
+                            new: false,
+                        },
+                    );
+                    ui.separator();
+                }
+
+                if changed {
+                    self.save_bookmarks();
+                }
+                self.track_scroll(ui, ScrollKey::Feed(Tab::Saved));
+            });
+    }
+
+    /// Renders a single item as the root of the view, with the chain of comments leading
+    /// down to it shown above, much like the site's permalink pages.
+    fn render_permalink(&mut self, ui: &mut egui::Ui, id: u32) {
+        ui.horizontal(|ui| {
+            if ui.button("⬅ Back").clicked() {
+                self.history.pop();
+            }
+            if ui.button("Close").clicked() {
+                self.history.clear();
+            }
+        });
+        ui.separator();
+
+        // Walk up from the item as far as its ancestors have loaded, nearest first.
+        let mut ancestors: Vec<LocalComment> = Vec::new();
+        let mut story: Option<LocalStory> = None;
+        let mut pending: Option<u32> = None;
+        let mut errored = false;
+        {
+            let data = self.data.lock().unwrap();
+            let mut next = Some(id);
+            while let Some(cur) = next.take() {
+                match (data.comments.get(&cur), data.items.get(&cur)) {
+                    (Some(CommentState::Loaded(c)), _) => {
+                        next = c.parent;
+                        ancestors.push(c.clone());
+                    }
+                    (_, Some(ItemState::Loaded(s))) => story = Some(s.clone()),
+                    (Some(CommentState::Errored), _) | (_, Some(ItemState::Errored)) => {
+                        errored = true
+                    }
+                    (Some(CommentState::Loading), _) | (_, Some(ItemState::Loading)) => {}
+                    (None, None) => pending = Some(cur),
+                }
+            }
+        }
+
+        if let Some(pending) = pending {
+            self.request_item(pending);
+        }
+
+        let op = story
+            .as_ref()
+            .and_then(|s| s.by.clone())
+            .unwrap_or_default();
+        let story_id = story.as_ref().map(|s| s.id);
+        let focused = if ancestors.is_empty() {
+            None
+        } else {
+            Some(ancestors.remove(0))
+        };
+
+        // Each view keeps its own scroll position, wherever it is opened from.
+        let view = ScrollKey::Item(id);
+        self.scroll_area(ui, view).show(ui, |ui| {
+            if let Some(s) = &story {
+                self.mark_read(s.id);
+                let header = self
+                    .selectable_frame(ui, s.id)
+                    .show(ui, |ui| self.render_story_header(ui, s, ""))
+                    .response;
+                self.track_selectable(
+                    &header,
+                    NavEntry {
+                        id: s.id,
+                        depth: 0,
+                        link: s.url.clone(),
+                        new: false,
+                    },
+                );
+                if let Some(text) = &s.text {
+                    if let Some(id) = render_html(ui, text, self.links_in_app()) {
+                        self.history.push(id);
+                    }
+                }
+                if let Some(parts) = &s.parts {
+                    self.render_poll_options(ui, parts);
+                }
+                if focused.is_none() {
+                    self.export_menu(ui, s);
+                    self.order_picker(ui, s.id);
+                }
+                ui.separator();
+            } else if errored {
+                ui.label("Errored.");
+            } else {
+                ui.label("Loading...");
             }
-            thread::sleep(Duration::from_secs(REFETCH_DELAY_SECONDS));
-        });
 
-        let data_show = Arc::clone(&self.data);
-        thread::spawn(move || loop {
-            let client = JsonClient::new();
-            let ids = client.show_stories();
-            if let Ok(ids) = ids {
-                let page;
-                {
-                    let data = data_show.lock().unwrap();
-                    page = data.show_page;
+            match &focused {
+                Some(c) => {
+                    for (depth, ancestor) in ancestors.iter().rev().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.add_space(depth as f32 * self.settings.indent);
+                            ui.vertical(|ui| self.render_comment(ui, ancestor, &op, false));
+                        });
+                        ui.separator();
+                    }
+                    ui.horizontal(|ui| {
+                        ui.add_space(ancestors.len() as f32 * self.settings.indent);
+                        let order =
+                            story_id.map_or_else(Default::default, |s| self.comment_order(s));
+                        ui.vertical(|ui| {
+                            self.render_comments(ui, &[c.id], &op, story_id, 1, order.nested())
+                        });
+                    });
                 }
-                for (idx, id) in ids.iter().take(WINDOW * (page + 1)).enumerate() {
-                    if let Ok(item) = client.item(*id) {
-                        let mut data = data_show.lock().unwrap();
-                        data.show.insert(idx, item);
+                None => {
+                    if let Some(s) = &story {
+                        self.render_thread(ui, s.kids.as_deref().unwrap_or_default(), &op, s.id);
                     }
                 }
-                let mut data = data_show.lock().unwrap();
-                data.show_ids = ids;
-                data.show_page = (data.show_page + 1) % 2;
             }
-            thread::sleep(Duration::from_secs(REFETCH_DELAY_SECONDS));
+            self.track_scroll(ui, view);
         });
+
+        if let Some(s) = &story {
+            // A focused comment counts as a visit, but only a whole thread is prefetched.
+            let kids = match focused {
+                None => s.kids.clone().unwrap_or_default(),
+                Some(_) => Vec::new(),
+            };
+            self.open_threads.push((s.id, kids));
+        }
     }
 
-    fn render_stories(&mut self, ui: &mut egui::Ui) {
-        egui::containers::ScrollArea::vertical()
-            .auto_shrink([false, false])
-            .show(ui, |ui| {
-                let current = self.data.lock().unwrap();
-                let items = match self.tab {
-                    Tab::Top => &current.top,
-                    Tab::New => &current.new,
-                    Tab::Show => &current.show,
-                };
+    /// Starts fetching an item of unknown type, unless it is already cached or on its way.
+    fn request_item(&self, id: u32) {
+        {
+            let mut data = self.data.lock().unwrap();
+            if data.comments.contains_key(&id) || data.items.contains_key(&id) {
+                return;
+            }
+            data.items.insert(id, ItemState::Loading);
+        }
 
-                let mut stories: Vec<(usize, LocalStory)> = items
-                    .iter()
-                    .filter_map(|(idx, i)| match i {
-                        Item::Story(s) => Some((*idx as usize, LocalStory::from_lib(s))),
-                        _ => None,
-                    })
-                    .collect();
-                stories.sort_by(|(a, _), (b, _)| a.cmp(b));
-                std::mem::drop(current);
-
-                stories.iter().for_each(|(idx, s)| {
-                    if let Some(title) = &s.title {
-                        if let Some(by) = &s.by {
-                            ui.horizontal_wrapped(|ui| {
-                                ui.label(egui::RichText::new(title).strong());
-                                if let Some(url) = &s.url {
-                                    if let Ok(u) = Url::parse(url) {
-                                        ui.horizontal(|ui| {
-                                            ui.spacing_mut().item_spacing.x = 0.0;
-                                            ui.label("(");
-                                            ui.hyperlink_to(
-                                                u.domain()
-                                                    .and_then(|s| Some(s.to_string()))
-                                                    .unwrap_or(url.clone()),
-                                                u.to_string(),
-                                            );
-                                            ui.label(")");
-                                        });
-                                    }
-                                }
-                            });
+        let data = Arc::clone(&self.data);
+        thread::spawn(move || {
+            fetch_item(&JsonClient::new(), &data, id);
+        });
+    }
 
-                            ui.horizontal(|ui| {
-                                ui.spacing_mut().item_spacing.x = 2.5;
-                                ui.label(format!("{} points", &s.score.unwrap_or(0)));
-                                ui.add(egui::widgets::Separator::default().vertical());
-                                ui.hyperlink_to(by, format!("{}/user?id={}", BASE_URL, by));
+    fn render_poll_options(&self, ui: &mut egui::Ui, parts: &[u32]) {
+        for (i, part) in parts.iter().enumerate() {
+            let state = self.data.lock().unwrap().items.get(part).cloned();
+            match state {
+                Some(ItemState::Loaded(opt)) => {
+                    // The API client drops the option text, so link out to it instead.
+                    ui.horizontal(|ui| {
+                        ui.hyperlink_to(
+                            format!("Option {}", i + 1),
+                            format!("{}/item?id={}", BASE_URL, opt.id),
+                        );
+                        ui.label(format!("{} points", opt.score.unwrap_or(0)));
+                    });
+                }
+                Some(ItemState::Errored) => {
+                    ui.label("Errored.");
+                }
+                Some(ItemState::Loading) => {
+                    ui.label("Loading...");
+                }
+                None => self.request_item(*part),
+            }
+        }
+    }
 
-                                ui.add(egui::widgets::Separator::default().vertical());
+    /// Renders a comment's byline and text, but not its replies.
+    fn render_comment(&mut self, ui: &mut egui::Ui, c: &LocalComment, op: &str, new: bool) {
+        ui.horizontal(|ui| {
+            ui.spacing_mut().item_spacing.x = 2.5;
+            if let Some(by) = &c.by {
+                ui.spacing_mut().item_spacing.x = 4.;
+                ui.hyperlink_to(by, format!("{}/user?id={}", BASE_URL, by));
 
-                                let now = SystemTime::now()
-                                    .duration_since(UNIX_EPOCH)
-                                    .expect("oops")
-                                    .as_secs();
+                if by.eq(op) {
+                    ui.code("op");
+                }
+            }
+            if new {
+                ui.label(
+                    egui::RichText::new("new")
+                        .strong()
+                        .color(ui.visuals().hyperlink_color),
+                );
+            }
 
-                                ui.label(format!(
-                                    "{}",
-                                    HumanTime::from_seconds((s.time as i64) - (now as i64))
-                                ));
+            ui.add(egui::widgets::Separator::default().vertical());
 
-                                ui.hyperlink_to("↗", format!("{}/item?id={}", BASE_URL, s.id));
-                            });
+            self.clock.label(ui, c.time);
 
-                            let kid_count = &s
-                                .kids
-                                .as_ref()
-                                .and_then(|k| Some(k.len()))
-                                .unwrap_or_default();
-
-                            egui::containers::CollapsingHeader::new(format!(
-                                "{} Comment{}",
-                                kid_count,
-                                if *kid_count != 1 { "s" } else { "" }
-                            ))
-                            .enabled(*kid_count > 0)
-                            .id_source(format!("{}-{}", idx, s.id))
-                            .show(ui, |ui| {
-                                if let Some(kids) = &s.kids {
-                                    self.render_comments(ui, kids, by);
-                                }
-                            });
+            ui.hyperlink_to("↗", format!("{}/item?id={}", BASE_URL, c.id));
 
-                            ui.separator();
+            if let Some(parent) = c.parent {
+                if ui.small_button("parent").clicked() {
+                    self.history.push(parent);
+                }
+            }
+            if self.history.last() != Some(&c.id) && ui.small_button("context").clicked() {
+                self.history.push(c.id);
+            }
+            self.bookmark_button(ui, c.id, || c.bookmark());
+        });
+
+        // Deleted and dead comments keep their place in the tree so that
+        // any replies can still be expanded beneath them.
+        if c.deleted {
+            ui.label(egui::RichText::new("[deleted]").weak());
+        } else if c.dead {
+            ui.label(egui::RichText::new("[dead]").weak());
+        } else {
+            let text = c.text.to_owned().unwrap_or_default();
+            if let Some(id) = render_html(ui, &text, self.links_in_app()) {
+                self.history.push(id);
+            }
+        }
+    }
+
+    fn comment_order(&self, story: u32) -> CommentOrder {
+        self.comment_orders.get(&story).copied().unwrap_or_default()
+    }
+
+    fn order_picker(&mut self, ui: &mut egui::Ui, story: u32) {
+        let mut order = self.comment_order(story);
+        ui.horizontal(|ui| {
+            ui.label("Show");
+            for option in CommentOrder::ALL {
+                ui.selectable_value(&mut order, option, option.name());
+            }
+        });
+        if order == CommentOrder::default() {
+            self.comment_orders.remove(&story);
+        } else {
+            self.comment_orders.insert(story, order);
+        }
+    }
+
+    /// Renders a story's comments the way the user chose for it, under an outline of the
+    /// top-level comments for finding a way around big threads.
+    fn render_thread(&mut self, ui: &mut egui::Ui, kids: &[u32], op: &str, story: u32) {
+        let order = self.comment_order(story);
+        if order != CommentOrder::Outline && !kids.is_empty() {
+            self.render_outline(ui, kids, story, order.nested());
+        }
+        if !matches!(order, CommentOrder::Timeline | CommentOrder::Outline) {
+            ui.horizontal(|ui| {
+                if ui.small_button("Collapse all").clicked() {
+                    self.set_subtree_open(kids, false);
+                }
+                if ui.small_button("Expand all").clicked() {
+                    self.set_subtree_open(kids, true);
+                }
+            });
+        }
+        match order {
+            CommentOrder::Timeline => self.render_timeline(ui, kids, op, story),
+            order => self.render_comments(ui, kids, op, Some(story), 1, order),
+        }
+    }
+
+    /// Lists the loaded top-level comments by author and first line. Clicking one selects it
+    /// and scrolls it into view.
+    fn render_outline(&mut self, ui: &mut egui::Ui, kids: &[u32], story: u32, order: CommentOrder) {
+        let data = Arc::clone(&self.data);
+        let loaded = {
+            let data = data.lock().unwrap();
+            kids.iter()
+                .filter(|id| matches!(data.comments.get(id), Some(CommentState::Loaded(_))))
+                .count()
+        };
+        egui::CollapsingHeader::new(format!("Outline ({})", loaded))
+            .id_source(("outline", story))
+            .show(ui, |ui| {
+                let entries: Vec<(u32, String, String)> = {
+                    let data = data.lock().unwrap();
+                    data.sorted_kids(kids, order)
+                        .iter()
+                        .filter_map(|id| match data.comments.get(id) {
+                            Some(CommentState::Loaded(c)) => Some(c),
+                            _ => None,
+                        })
+                        .filter(|c| {
+                            !c.deleted
+                                && (self.show_muted || !self.mutes.mutes_user(c.by.as_deref()))
+                        })
+                        .map(|c| {
+                            let label = self
+                                .outline_lines
+                                .entry(c.id)
+                                .or_insert_with(|| outline_line(c.text.as_deref()));
+                            (c.id, c.by.clone().unwrap_or_default(), label.clone())
+                        })
+                        .collect()
+                };
+                for (id, by, label) in entries {
+                    ui.horizontal(|ui| {
+                        ui.label(egui::RichText::new(by).strong());
+                        if ui
+                            .selectable_label(self.selected == Some(id), label)
+                            .clicked()
+                        {
+                            self.selected = Some(id);
+                            self.scroll_to_selected = true;
                         }
+                    });
+                }
+            });
+    }
+
+    /// Renders every loaded comment in a thread, oldest first, each saying whom it answers.
+    /// The thread is prefetched while shown like this, so the rest arrive as it is read.
+    fn render_timeline(&mut self, ui: &mut egui::Ui, kids: &[u32], op: &str, story: u32) {
+        let (comments, known, loaded) = {
+            let mut data = self.data.lock().unwrap();
+            let (known, loaded) = data.thread_size(kids);
+            // Sorting the whole thread every frame adds up, so it's only redone as more loads.
+            if self.timelines.get(&story).map(|(count, _)| *count) != Some(loaded) {
+                let ids = data.timeline(kids).iter().map(|c| c.id).collect();
+                self.timelines.insert(story, (loaded, ids));
+            }
+            let comments: Vec<LocalComment> = self.timelines[&story]
+                .1
+                .iter()
+                .filter_map(|id| match data.comments.get(id) {
+                    Some(CommentState::Loaded(c)) => Some(c.clone()),
+                    _ => None,
+                })
+                .collect();
+            (comments, known, loaded)
+        };
+        if loaded < known {
+            ui.label(
+                egui::RichText::new(format!("{} of {}+ comments loaded", loaded, known)).weak(),
+            );
+        }
+        let authors: HashMap<u32, &str> = comments
+            .iter()
+            .map(|c| (c.id, c.by.as_deref().unwrap_or("[deleted]")))
+            .collect();
+
+        let mut hidden = 0;
+        for c in &comments {
+            if self.is_hidden(c) {
+                if !self.hides_dead(c) {
+                    hidden += 1;
+                }
+                continue;
+            }
+
+            let new = self
+                .visits
+                .get_mut(&story)
+                .map_or(false, |visit| visit.is_new(c.time));
+            let comment = self
+                .selectable_frame(ui, c.id)
+                .show(ui, |ui| {
+                    if let Some(by) = c.parent.and_then(|parent| authors.get(&parent)) {
+                        ui.small(format!("in reply to {}", by));
                     }
-                });
+                    self.render_comment(ui, c, op, new)
+                })
+                .response;
+            self.track_selectable(
+                &comment,
+                NavEntry {
+                    id: c.id,
+                    depth: 1,
+                    link: None,
+                    new,
+                },
+            );
+            ui.separator();
+        }
 
-                // let margin = ui.visuals().clip_rect_margin;
-                // let current_scroll = ui.clip_rect().top() - ui.min_rect().top() + margin;
-                // let max_scroll = ui.min_rect().height() - ui.clip_rect().height() + 2.0 * margin;
-
-                // if current_scroll == max_scroll {
-                //     println!("yuip");
-                //     match self.tab {
-                //         Tab::Top => {
-                //             self.fetch_top();
-                //             // DEADLOCK!
-                //             let mut data = self.data.lock().unwrap();
-                //             data.top_page = (data.top_page + 1) % 4;
-                //         }
-                //         Tab::New => {
-                //             self.fetch_new();
-                //             let mut data = self.data.lock().unwrap();
-                //             data.new_page = (data.new_page + 1) % 4;
-                //         }
-                //         Tab::Show => {}
-                //     };
-                // }
-
-                // ui.vertical_centered(|ui| {
-                //     let (page, count) = match self.tab {
-                //         Tab::Top => (self.top_page, self.top_ids.len()),
-                //         Tab::New => (self.new_page, self.new_ids.len()),
-                //         Tab::Show => (self.show_page, self.show_ids.len()),
-                //     };
-                //     if count > 0 && WINDOW * page >= count {
-                //         ui.label("All done!");
-                //     } else if ui.button("Load more").clicked() {
-                //         self.fetch_stories();
-                //     }
-                // });
-            })
+        if hidden > 0 {
+            self.muted_toggle(ui, hidden);
+        }
+    }
+
+    /// Whether a comment is left out, being dead or by a muted user.
+    fn is_hidden(&self, c: &LocalComment) -> bool {
+        self.hides_dead(c) || (!self.show_muted && self.mutes.mutes_user(c.by.as_deref()))
+    }
+
+    /// Whether a comment is left out for being dead. Unlike muted ones, these aren't counted
+    /// towards the "hidden" toggle, which can't bring them back.
+    fn hides_dead(&self, c: &LocalComment) -> bool {
+        c.dead && self.settings.hide_dead_comments
     }
 
-    fn render_comments(&self, ui: &mut egui::Ui, kids: &Vec<u32>, op: &String) {
+    /// Renders a level of comments under `story`, which is `None` if it hasn't loaded yet.
+    fn render_comments(
+        &mut self,
+        ui: &mut egui::Ui,
+        kids: &[u32],
+        op: &str,
+        story: Option<u32>,
+        depth: usize,
+        order: CommentOrder,
+    ) {
         let data = Arc::clone(&self.data);
-        lazy_static! {
-            static ref RE: Regex =
-                Regex::new(r#"<a\s+href=(?:"([^"]+)"|'([^']+)').*?>(.*?)</a>"#).unwrap();
-        }
+        let mut hidden = 0;
+        let kids = data.lock().unwrap().sorted_kids(kids, order);
+        // Hidden comments are skipped, so the last one drawn may come before the end.
+        let last_shown = {
+            let data = data.lock().unwrap();
+            kids.iter().rposition(|k| match data.comments.get(k) {
+                Some(CommentState::Loaded(c)) => !self.is_hidden(c),
+                _ => true,
+            })
+        };
 
         for (i, k) in kids.iter().enumerate() {
             let comment: Option<CommentState>;
             {
                 let data = data.lock().unwrap();
-                comment = data.comments.get(k).and_then(|c| Some(c.to_owned()));
+                comment = data.comments.get(k).cloned();
             }
 
             match comment {
@@ -347,71 +2782,79 @@ impl YReader {
                     ui.label("Loading...");
                 }
                 Some(CommentState::Loaded(c)) => {
-                    ui.horizontal(|ui| {
-                        ui.spacing_mut().item_spacing.x = 2.5;
-                        if let Some(by) = &c.by {
-                            ui.spacing_mut().item_spacing.x = 4.;
-                            ui.hyperlink_to(by, format!("{}/user?id={}", BASE_URL, by));
-
-                            if by.eq(op) {
-                                ui.code("op");
-                            }
+                    if self.is_hidden(&c) {
+                        if !self.hides_dead(&c) {
+                            hidden += 1;
                         }
+                        continue;
+                    }
 
-                        ui.add(egui::widgets::Separator::default().vertical());
-
-                        let now = SystemTime::now()
-                            .duration_since(UNIX_EPOCH)
-                            .expect("oops")
-                            .as_secs();
-
-                        ui.label(format!(
-                            "{}",
-                            HumanTime::from_seconds((c.time as i64) - (now as i64))
-                        ));
-
-                        ui.hyperlink_to("↗", format!("{}/item?id={}", BASE_URL, c.id));
-                    });
-
-                    let text = c.text.to_owned().unwrap_or_default();
-                    let decoded = html_escape::decode_html_entities(&text).to_string();
-
-                    ui.horizontal_wrapped(|ui| {
-                        ui.spacing_mut().item_spacing.y = 10.;
-                        decoded.split("<p>").into_iter().for_each(|part| {
-                            if RE.is_match(part) {
-                                for piece in RE.captures_iter(part) {
-                                    // TODO: include non-link text from this line
-                                    if let Some(url) = &piece.get(1) {
-                                        if let Some(label) = &piece.get(3) {
-                                            ui.hyperlink_to(label.as_str(), url.as_str());
-                                        } else {
-                                            ui.hyperlink(url.as_str());
-                                        }
-                                    }
-                                }
-                            } else {
-                                ui.label(part);
-                            }
-
-                            ui.end_row();
-                        });
-                    });
-
-                    let is_last = i == kids.len() - 1;
+                    let new = story
+                        .and_then(|story| self.visits.get_mut(&story))
+                        .map_or(false, |visit| visit.is_new(c.time));
+                    let comment = self
+                        .selectable_frame(ui, c.id)
+                        .show(ui, |ui| self.render_comment(ui, &c, op, new))
+                        .response;
+                    self.track_selectable(
+                        &comment,
+                        NavEntry {
+                            id: c.id,
+                            depth,
+                            link: None,
+                            new,
+                        },
+                    );
+
+                    let is_last = Some(i) == last_shown;
                     if let Some(kids) = &c.kids {
                         ui.add_space(2.);
-                        let kid_count = kids.len();
-                        egui::containers::CollapsingHeader::new(format!(
-                            "{} Repl{}",
-                            kid_count,
-                            if kid_count == 1 { "y" } else { "ies" }
-                        ))
-                        .enabled(kid_count > 0)
-                        .id_source(c.id)
-                        .show(ui, |ui| {
-                            self.render_comments(ui, &kids, op);
-                        });
+                        // Until the whole subtree has been fetched we only know a lower bound.
+                        let (known, loaded) = self.data.lock().unwrap().thread_size(kids);
+                        let label = format!(
+                            "{}{} Repl{}",
+                            known,
+                            if loaded < known { "+" } else { "" },
+                            if known == 1 { "y" } else { "ies" }
+                        );
+                        if order == CommentOrder::Outline {
+                            // Replies are read by opening the comment as the root of the view.
+                            if ui.small_button(label).clicked() {
+                                self.history.push(c.id);
+                            }
+                        } else {
+                            // The indent guide is drawn with the parent's stroke, so color it
+                            // for this depth and put it back for the replies themselves.
+                            let stroke = ui.visuals().widgets.noninteractive.bg_stroke;
+                            if let Some(guide) = self.theme().depth_stroke(depth) {
+                                ui.visuals_mut().widgets.noninteractive.bg_stroke = guide;
+                            }
+                            let toggle = self.take_toggle(c.id);
+                            let limit = self.settings.collapse_depth;
+                            let expand =
+                                self.settings.expand_replies && (limit == 0 || depth < limit);
+                            let replies = egui::containers::CollapsingHeader::new(label)
+                                .enabled(known > 0)
+                                .default_open(self.initially_open(c.id, expand))
+                                .id_source(c.id)
+                                .open(toggle)
+                                .show(ui, |ui| {
+                                    ui.visuals_mut().widgets.noninteractive.bg_stroke = stroke;
+                                    self.render_comments(ui, kids, op, story, depth + 1, order);
+                                });
+                            ui.visuals_mut().widgets.noninteractive.bg_stroke = stroke;
+                            replies.header_response.clone().context_menu(|ui| {
+                                if ui.button("Collapse subtree").clicked() {
+                                    self.set_subtree_open(&[c.id], false);
+                                    ui.close_menu();
+                                }
+                                if ui.button("Expand subtree").clicked() {
+                                    self.set_subtree_open(&[c.id], true);
+                                    ui.close_menu();
+                                }
+                            });
+                            self.track_header(c.id, &replies, toggle);
+                        }
                     }
 
                     ui.add_space(2.);
@@ -425,85 +2868,37 @@ impl YReader {
                 }
                 _ => {
                     ui.label("Starting load...");
-                    let data = Arc::clone(&self.data);
-                    let id = k.clone();
+                    self.comment_loader
+                        .get_or_insert_with(|| Prefetch::new(&data, false))
+                        .enqueue(&[*k]);
+                }
+            }
+        }
 
-                    {
-                        let mut data = data.lock().unwrap();
-                        data.comments.insert(id, CommentState::Loading);
-                    }
+        if hidden > 0 {
+            self.muted_toggle(ui, hidden);
+        }
+    }
 
-                    thread::spawn(move || {
-                        let client = JsonClient::new();
-                        let comment = client.item(id);
+    fn mark_read(&mut self, id: u32) {
+        self.read.entry(id).or_insert_with(unix_now);
+    }
 
-                        let mut data = data.lock().unwrap();
-                        match comment {
-                            Ok(Item::Comment(c)) => {
-                                data.comments
-                                    .insert(id, CommentState::Loaded(LocalComment::from_lib(&c)));
-                            }
-                            _ => {
-                                data.comments.insert(id, CommentState::Errored);
-                            }
-                        }
-                    });
-                }
+    /// Says how many items the mute rules hid here, with a button to show or hide them again.
+    fn muted_toggle(&mut self, ui: &mut egui::Ui, hidden: usize) {
+        if self.show_muted {
+            if ui.small_button("Hide muted").clicked() {
+                self.show_muted = false;
             }
+        } else if hidden > 0
+            && ui
+                .small_button(format!("{} hidden", hidden))
+                .on_hover_text("Show items hidden by your mute rules")
+                .clicked()
+        {
+            self.show_muted = true;
         }
     }
-
-    // fn fetch_top(&self) {
-    //     let data_top = Arc::clone(&self.data);
-
-    //     thread::spawn(move || {
-    //         let client = JsonClient::new();
-    //         let ids = client.top_stories();
-    //         if let Ok(ids) = ids {
-    //             let page;
-    //             {
-    //                 let data = data_top.lock().unwrap();
-    //                 page = data.top_page;
-    //             }
-    //             for (idx, id) in ids.iter().take(WINDOW * (page + 1)).enumerate() {
-    //                 if let Ok(item) = client.item(*id) {
-    //                     let mut data = data_top.lock().unwrap();
-    //                     data.top.insert(idx, item);
-    //                 }
-    //             }
-    //             let mut data = data_top.lock().unwrap();
-    //             data.top_ids = ids;
-    //             // data.top_page = (data.top_page + 1) % 4;
-    //         }
-    //         println!("Fetched top");
-    //     });
-    // }
-
-    // fn fetch_new(&self) {
-    //     let data_new = Arc::clone(&self.data);
-
-    //     thread::spawn(move || {
-    //         let client = JsonClient::new();
-    //         let ids = client.new_stories();
-    //         if let Ok(ids) = ids {
-    //             let page;
-    //             {
-    //                 let data = data_new.lock().unwrap();
-    //                 page = data.new_page;
-    //             }
-    //             for (idx, id) in ids.iter().take(WINDOW * (page + 1)).enumerate() {
-    //                 if let Ok(item) = client.item(*id) {
-    //                     let mut data = data_new.lock().unwrap();
-    //                     data.new.insert(idx, item);
-    //                 }
-    //             }
-    //             let mut data = data_new.lock().unwrap();
-    //             data.new_ids = ids;
-    //             // data.new_page = (data.new_page + 1) % 4;
-    //         }
-    //         println!("Fetched new");
-    //     });
-    // }
 }
 
 impl Default for YReader {
@@ -513,8 +2908,72 @@ impl Default for YReader {
             authed: false,
             show_login: false,
             show_settings: false,
+            read: HashMap::new(),
+            hide_read: false,
+            visits: HashMap::new(),
+            open_state: HashMap::new(),
+            comment_orders: HashMap::new(),
+            timelines: HashMap::new(),
+            scroll_offsets: HashMap::new(),
+            scroll_restores: HashMap::new(),
+            shown_views: (Vec::new(), Vec::new()),
             tab: Tab::Top,
+            history: Vec::new(),
+            thread_tabs: Vec::new(),
+            active_thread: None,
+            open_input: String::new(),
             data: Arc::new(Mutex::new(Data::new())),
+            prefetches: HashMap::new(),
+            comment_loader: None,
+            show_help: false,
+            palette: CommandPalette::default(),
+            filter: String::new(),
+            focus_search: false,
+            mutes: MuteRules::default(),
+            mutes_error: None,
+            show_muted: false,
+            watch: WatchList::default(),
+            watch_error: None,
+            show_alerts: false,
+            open_stories: HashSet::new(),
+            open_threads: Vec::new(),
+            split: false,
+            queued_action: None,
+            bookmarks: Bookmarks::default(),
+            bookmarks_error: None,
+            saved_tag: None,
+            saved_oldest_first: false,
+            tag_drafts: HashMap::new(),
+            export_status: None,
+            exports: HashMap::new(),
+            export_format: Format::Markdown,
+            import_username: String::new(),
+            imported_list: List::Favorites,
+            settings: Settings::default(),
+            settings_error: None,
+            applied: None,
+            themes: theme::builtin(),
+            themes_error: None,
+            fonts_error: None,
+            session_error: None,
+            session_saved: 0.,
+            clock: Clock::new(
+                unix_now(),
+                timestamp::Style::Relative,
+                timestamp::DEFAULT_FORMAT,
+            ),
+            native_pixels_per_point: 1.0,
+            refresh: Arc::new(Condvar::new()),
+            keymap: Keymap::default(),
+            keymap_error: None,
+            recording: None,
+            selected: None,
+            scroll_to_selected: false,
+            toggle_request: None,
+            header_requests: HashMap::new(),
+            outline_lines: HashMap::new(),
+            nav_order: Vec::new(),
+            expanded: HashSet::new(),
         }
     }
 }
@@ -527,8 +2986,8 @@ impl epi::App for YReader {
     /// Called once before the first frame.
     fn setup(
         &mut self,
-        _ctx: &egui::CtxRef,
-        _frame: &epi::Frame,
+        ctx: &egui::CtxRef,
+        frame: &epi::Frame,
         _storage: Option<&dyn epi::Storage>,
     ) {
         // Load previous app state (if any).
@@ -537,6 +2996,60 @@ impl epi::App for YReader {
         if let Some(storage) = _storage {
             *self = epi::get_value(storage, epi::APP_KEY).unwrap_or_default()
         }
+        self.load_session();
+        let cutoff = unix_now().saturating_sub(READ_RETENTION_DAYS * 24 * 60 * 60);
+        self.read.retain(|_, opened| *opened >= cutoff);
+        self.data
+            .lock()
+            .unwrap()
+            .alerted
+            .retain(|_, matched| *matched >= cutoff);
+        self.visits.retain(|_, visit| visit.started >= cutoff);
+        self.open_state.retain(|_, (_, changed)| *changed >= cutoff);
+        let read = &self.read;
+        self.comment_orders
+            .retain(|story, _| read.contains_key(story));
+        self.scroll_offsets.retain(|_, (_, seen)| *seen >= cutoff);
+        self.active_thread = self.active_thread.filter(|&i| i < self.thread_tabs.len());
+        if let Some(active) = self.active_thread {
+            self.history = self.thread_tabs[active].history.clone();
+        }
+
+        match config::load(settings::CONFIG_FILE) {
+            Ok(Some(settings)) => self.settings = settings,
+            Ok(None) => {}
+            Err(e) => self.settings_error = Some(format!("Couldn't read settings: {}", e)),
+        }
+        self.load_themes();
+        if let Some(native) = frame.info().native_pixels_per_point {
+            self.native_pixels_per_point = native;
+        }
+        self.apply_settings(ctx);
+        self.tab = self.settings.default_tab;
+
+        match config::load(keys::CONFIG_FILE) {
+            Ok(Some(keymap)) => self.keymap = keymap,
+            Ok(None) => {}
+            Err(e) => self.keymap_error = Some(format!("Couldn't read key bindings: {}", e)),
+        }
+        match config::load(mute::CONFIG_FILE) {
+            Ok(Some(mutes)) => self.mutes = mutes,
+            Ok(None) => {}
+            Err(e) => self.mutes_error = Some(format!("Couldn't read mute rules: {}", e)),
+        }
+        self.mutes.compile();
+        match config::load(watch::CONFIG_FILE) {
+            Ok(Some(list)) => self.watch = list,
+            Ok(None) => {}
+            Err(e) => self.watch_error = Some(format!("Couldn't read watch list: {}", e)),
+        }
+        self.data.lock().unwrap().watch_terms = self.watch.terms.clone();
+        match config::load(bookmarks::DATA_FILE) {
+            Ok(Some(bookmarks)) => self.bookmarks = bookmarks,
+            Ok(None) => {}
+            Err(e) => self.bookmarks_error = Some(format!("Couldn't read bookmarks: {}", e)),
+        }
+        self.load_imported();
 
         self.init();
     }
@@ -548,39 +3061,145 @@ impl epi::App for YReader {
         epi::set_value(storage, epi::APP_KEY, self);
     }
 
+    fn on_exit(&mut self) {
+        self.save_session();
+    }
+
     /// Called each time the UI needs repainting, which may be many times per second.
     /// Put your widgets into a `SidePanel`, `TopPanel`, `CentralPanel`, `Window` or `Area`.
     fn update(&mut self, ctx: &egui::CtxRef, _frame: &epi::Frame) {
+        self.clock = Clock::new(
+            unix_now(),
+            self.settings.timestamps,
+            &self.settings.time_format,
+        );
+        self.handle_keys(ctx);
+        let mut settings_changed = false;
+        let mut feed_clicked = None;
+        let mut thread_clicked = None;
+        let mut thread_closed = None;
+        let mut themes_reloaded = false;
+        let opposite_theme = self.theme().opposite();
+
         let Self {
             auth,
             authed,
             show_login,
             show_settings,
+            read: _,
+            hide_read: _,
+            visits: _,
+            open_state: _,
+            comment_orders: _,
+            timelines: _,
+            scroll_offsets: _,
+            scroll_restores: _,
+            shown_views: _,
             tab,
-            data: _,
+            history,
+            thread_tabs,
+            active_thread,
+            open_input,
+            data,
+            prefetches: _,
+            comment_loader: _,
+            show_help,
+            palette: _,
+            filter: _,
+            focus_search: _,
+            mutes,
+            mutes_error,
+            show_muted: _,
+            watch,
+            watch_error,
+            show_alerts,
+            open_stories: _,
+            open_threads: _,
+            split: _,
+            queued_action: _,
+            bookmarks: _,
+            bookmarks_error: _,
+            saved_tag: _,
+            saved_oldest_first: _,
+            tag_drafts: _,
+            export_status: _,
+            exports: _,
+            export_format: _,
+            import_username: _,
+            imported_list: _,
+            settings,
+            settings_error,
+            applied: _,
+            themes,
+            themes_error,
+            fonts_error,
+            session_error,
+            session_saved: _,
+            clock: _,
+            native_pixels_per_point: _,
+            refresh: _,
+            keymap,
+            keymap_error,
+            recording,
+            selected: _,
+            scroll_to_selected: _,
+            toggle_request: _,
+            header_requests: _,
+            outline_lines: _,
+            nav_order: _,
+            expanded: _,
         } = self;
 
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
-            lazy_static! {
-                static ref SELECTION_STYLE: egui::style::Selection = egui::style::Selection {
-                    bg_fill: egui::Color32::from_rgb(215, 101, 46),
-                    stroke: egui::Stroke {
-                        width: 1.,
-                        color: egui::Color32::WHITE,
-                    },
-                };
-            }
-            ui.visuals_mut().selection = *SELECTION_STYLE;
             ui.spacing_mut().item_spacing.y = 4.;
 
             ui.horizontal_wrapped(|ui| {
                 ui.heading("Y Reader");
 
                 ui.add(egui::widgets::Separator::default().vertical());
-                ui.selectable_value(tab, Tab::Top, "Top");
-                ui.selectable_value(tab, Tab::New, "New");
-                ui.selectable_value(tab, Tab::Show, "Show");
-                egui::widgets::global_dark_light_mode_switch(ui);
+                for value in Tab::ALL {
+                    if ui.selectable_label(*tab == value, value.name()).clicked() {
+                        feed_clicked = Some(value);
+                    }
+                }
+                if ui
+                    .small_button("?")
+                    .on_hover_text("Keyboard shortcuts")
+                    .clicked()
+                {
+                    *show_help = !*show_help;
+                }
+                let (icon, hint) = if opposite_theme == "Light" {
+                    ("☀", "Switch to light mode")
+                } else {
+                    ("🌙", "Switch to dark mode")
+                };
+                if ui.small_button(icon).on_hover_text(hint).clicked() {
+                    settings.theme = opposite_theme;
+                    settings_changed = true;
+                }
+
+                let alerts = data.lock().unwrap().alerts.len();
+                if alerts > 0
+                    && ui
+                        .button(egui::RichText::new(format!("🔔 {}", alerts)).color(watch::BADGE_COLOR))
+                        .on_hover_text("Watch list alerts")
+                        .clicked()
+                {
+                    *show_alerts = !*show_alerts;
+                }
+
+                let open = ui.add(
+                    egui::TextEdit::singleline(open_input)
+                        .hint_text("Open item id or URL")
+                        .desired_width(140.),
+                );
+                if open.lost_focus() && ui.input().key_pressed(egui::Key::Enter) {
+                    if let Some(id) = parse_item_id(open_input) {
+                        history.push(id);
+                        open_input.clear();
+                    }
+                }
 
                 ui.with_layout(egui::Layout::right_to_left(), |ui| {
                     if ui.button("Settings").clicked() {
@@ -591,7 +3210,74 @@ impl epi::App for YReader {
                         .open(show_settings)
                         .vscroll(true)
                         .show(ctx, |ui| {
-                            ctx.settings_ui(ui);
+                            settings_changed |= settings.ui(ui, themes);
+                            if let Some(path) = config::path(settings::CONFIG_FILE) {
+                                ui.small(format!("Saved to {}", path.display()));
+                            }
+                            if let Some(error) = settings_error {
+                                ui.colored_label(egui::Color32::RED, error.as_str());
+                            }
+                            if let Some(error) = fonts_error {
+                                ui.colored_label(egui::Color32::RED, error.as_str());
+                            }
+                            if let Some(error) = session_error {
+                                ui.colored_label(egui::Color32::RED, error.as_str());
+                            }
+                            ui.separator();
+
+                            egui::CollapsingHeader::new("Themes").show(ui, |ui| {
+                                ui.label("Add your own themes to the themes file; changes show as soon as it is reloaded.");
+                                if ui.button("Reload themes").clicked() {
+                                    themes_reloaded = true;
+                                }
+                                if let Some(path) = config::path(theme::CONFIG_FILE) {
+                                    ui.small(format!("Read from {}", path.display()));
+                                }
+                                if let Some(error) = themes_error {
+                                    ui.colored_label(egui::Color32::RED, error.as_str());
+                                }
+                            });
+                            egui::CollapsingHeader::new("Keyboard shortcuts").show(ui, |ui| {
+                                if keymap.settings_ui(ui, recording) {
+                                    *keymap_error = config::save(keys::CONFIG_FILE, keymap)
+                                        .err()
+                                        .map(|e| e.to_string());
+                                }
+                                if let Some(path) = config::path(keys::CONFIG_FILE) {
+                                    ui.small(format!("Saved to {}", path.display()));
+                                }
+                                if let Some(error) = keymap_error {
+                                    ui.colored_label(egui::Color32::RED, error.as_str());
+                                }
+                            });
+                            egui::CollapsingHeader::new("Muted").show(ui, |ui| {
+                                if mutes.settings_ui(ui) {
+                                    *mutes_error = config::save(mute::CONFIG_FILE, mutes)
+                                        .err()
+                                        .map(|e| e.to_string());
+                                }
+                                if let Some(path) = config::path(mute::CONFIG_FILE) {
+                                    ui.small(format!("Saved to {}", path.display()));
+                                }
+                                if let Some(error) = mutes_error {
+                                    ui.colored_label(egui::Color32::RED, error.as_str());
+                                }
+                            });
+                            egui::CollapsingHeader::new("Watch list").show(ui, |ui| {
+                                ui.label("Stories with these words in the title get a badge and raise an alert.");
+                                if watch.settings_ui(ui) {
+                                    data.lock().unwrap().watch_terms = watch.terms.clone();
+                                    *watch_error = config::save(watch::CONFIG_FILE, watch)
+                                        .err()
+                                        .map(|e| e.to_string());
+                                }
+                                if let Some(path) = config::path(watch::CONFIG_FILE) {
+                                    ui.small(format!("Saved to {}", path.display()));
+                                }
+                                if let Some(error) = watch_error {
+                                    ui.colored_label(egui::Color32::RED, error.as_str());
+                                }
+                            });
                         });
 
                     if auth.is_none() || !*authed {
@@ -652,8 +3338,50 @@ impl epi::App for YReader {
                         });
                 }
             });
+
+            if !thread_tabs.is_empty() {
+                ui.horizontal_wrapped(|ui| {
+                    for (i, thread) in thread_tabs.iter().enumerate() {
+                        let title = thread.title.as_deref().unwrap_or("Loading…");
+                        let mut label: String = title.chars().take(THREAD_TAB_TITLE_CHARS).collect();
+                        if label.len() < title.len() {
+                            label.push('…');
+                        }
+                        let tab = ui
+                            .selectable_label(*active_thread == Some(i), label)
+                            .on_hover_text(title);
+                        if tab.clicked() {
+                            thread_clicked = Some(i);
+                        }
+                        if tab.middle_clicked()
+                            || ui.small_button("✖").on_hover_text("Close").clicked()
+                        {
+                            thread_closed = Some(i);
+                        }
+                        ui.add(egui::widgets::Separator::default().vertical());
+                    }
+                });
+            }
         });
 
+        if let Some(tab) = feed_clicked {
+            self.show_tab(tab);
+        }
+        if let Some(index) = thread_clicked {
+            self.switch_thread(Some(index));
+        }
+        if let Some(index) = thread_closed {
+            self.close_thread(index);
+        }
+        if themes_reloaded {
+            self.load_themes();
+            ctx.set_visuals(self.theme().visuals());
+        }
+        if settings_changed {
+            self.apply_settings(ctx);
+            self.save_settings();
+        }
+
         egui::TopBottomPanel::bottom("bottom_panel").show(ctx, |ui| {
             ui.centered_and_justified(|ui| {
                 let data = &self.data.lock().unwrap();
@@ -661,17 +3389,241 @@ impl epi::App for YReader {
                     Tab::Top => data.top.len(),
                     Tab::New => data.new.len(),
                     Tab::Show => data.show.len(),
+                    Tab::Favorites => match self.imported_list {
+                        List::Favorites => data.favorite_ids.len(),
+                        List::Upvoted => data.upvoted_ids.len(),
+                    },
+                    Tab::Saved => self.bookmarks.len(),
                 };
                 ui.small(format!("{} items", count));
             });
         });
 
+        self.nav_order.clear();
+        let width = ctx.available_rect().width();
+        self.split = self.settings.split_pane && width >= SPLIT_MIN_WIDTH;
+        if self.split {
+            egui::SidePanel::left("story_list")
+                .resizable(true)
+                .default_width(width * 0.4)
+                .width_range(280.0..=width - 320.)
+                .show(ctx, |ui| {
+                    ui.spacing_mut().indent = self.settings.indent;
+                    if self.tab == Tab::Saved {
+                        self.render_saved(ui);
+                    } else {
+                        self.render_stories(ui);
+                    }
+                });
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
-            // ui.visuals_mut().hyperlink_color = egui::Color32::from_rgb(255, 101, 0);
-            ui.visuals_mut().hyperlink_color = egui::Color32::from_rgb(205, 91, 36);
+            ui.spacing_mut().indent = self.settings.indent;
             // The central panel the region left after adding TopPanel's and SidePanel's
             egui::warn_if_debug_build(ui);
-            self.render_stories(ui);
+            if let Some(&id) = self.history.last() {
+                self.render_permalink(ui, id);
+            } else if self.split {
+                ui.centered_and_justified(|ui| {
+                    ui.label(egui::RichText::new("Select a story to read its comments").weak());
+                });
+            } else if self.tab == Tab::Saved {
+                self.render_saved(ui);
+            } else {
+                self.render_stories(ui);
+            }
         });
+        self.sync_open_threads();
+        self.finish_exports();
+        if !self.exports.is_empty() {
+            ctx.request_repaint();
+        }
+        // Requests for items that weren't on screen this frame are dropped.
+        self.scroll_to_selected = false;
+        self.toggle_request = None;
+
+        let mut show_help = self.show_help;
+        egui::Window::new("Keyboard shortcuts")
+            .open(&mut show_help)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("shortcuts").striped(true).show(ui, |ui| {
+                    for action in Action::ALL {
+                        let bindings: Vec<String> = self
+                            .keymap
+                            .bindings(action)
+                            .iter()
+                            .map(|binding| binding.to_string())
+                            .collect();
+                        ui.label(action.description());
+                        ui.code(bindings.join(" / "));
+                        ui.end_row();
+                    }
+                });
+            });
+        self.show_help &= show_help;
+
+        let mut show_alerts = self.show_alerts;
+        let mut opened = None;
+        egui::Window::new("Alerts")
+            .open(&mut show_alerts)
+            .vscroll(true)
+            .show(ctx, |ui| {
+                let mut data = self.data.lock().unwrap();
+                if data.alerts.is_empty() {
+                    ui.label(egui::RichText::new("Nothing new on your watch list").weak());
+                }
+
+                let mut dismissed = None;
+                egui::Grid::new("alerts").striped(true).show(ui, |ui| {
+                    for (i, alert) in data.alerts.iter().enumerate().rev() {
+                        self.clock.label(ui, alert.time);
+                        ui.label(watch::badge(&alert.term));
+                        let title = egui::Label::new(egui::RichText::new(&alert.title).strong())
+                            .sense(egui::Sense::click());
+                        if ui.add(title).on_hover_text("Open thread").clicked() {
+                            opened = Some(alert.id);
+                        }
+                        if ui.small_button("Dismiss").clicked() {
+                            dismissed = Some(i);
+                        }
+                        ui.end_row();
+                    }
+                });
+                if let Some(i) = dismissed {
+                    data.alerts.remove(i);
+                }
+                if !data.alerts.is_empty() && ui.button("Dismiss all").clicked() {
+                    data.alerts.clear();
+                }
+            });
+        self.show_alerts = show_alerts;
+        if let Some(id) = opened {
+            self.show_thread(id);
+        }
+
+        if let Some(command) = self.palette.show(ctx, &self.keymap, parse_item_id) {
+            match command {
+                Command::Run(action) => self.run_action(ctx, action),
+                Command::OpenItem(id) => self.history.push(id),
+            }
+        }
+
+        self.sync_thread_tabs();
+        let (last_frame, this_frame) = &mut self.shown_views;
+        *last_frame = std::mem::take(this_frame);
+
+        let now = ctx.input().time;
+        if now - self.session_saved >= SESSION_SAVE_SECS {
+            self.session_saved = now;
+            self.save_session();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comment(id: u32, time: u64, kids: &[u32]) -> CommentState {
+        CommentState::Loaded(LocalComment {
+            id,
+            by: Some("someone".to_string()),
+            time,
+            kids: Some(kids.to_vec()),
+            parent: None,
+            text: Some("Hi".to_string()),
+            deleted: false,
+            dead: false,
+        })
+    }
+
+    /// A story with one old top-level comment, whose replies start out collapsed and were
+    /// never drawn: one posted before the last visit and one after it.
+    fn thread() -> Data {
+        let mut data = Data::new();
+        data.set_comment(1, comment(1, 50, &[2, 3]));
+        data.set_comment(2, comment(2, 80, &[]));
+        data.set_comment(3, comment(3, 150, &[]));
+        data
+    }
+
+    #[test]
+    fn new_comments_count_whether_or_not_they_were_drawn() {
+        let mut data = thread();
+        let times = data.comment_times(100, &[1]).to_vec();
+        assert_eq!(times, [50, 80, 150]);
+
+        let mut visit = Visit {
+            started: 100,
+            previous: None,
+        };
+        // Nothing is new during a first visit. Once it's closed, only the reply posted since it
+        // began is waiting, not every comment that was never drawn.
+        assert_eq!(visit.new_count(&times, true), 0);
+        assert!(!visit.is_new(150));
+        assert_eq!(visit.new_count(&times, false), 1);
+
+        visit.begin(200);
+        assert_eq!(visit.new_count(&times, true), 1);
+        assert!(visit.is_new(150));
+        assert!(!visit.is_new(80));
+        assert!(!visit.is_new(50));
+        assert_eq!(visit.new_count(&times, false), 0);
+    }
+
+    #[test]
+    fn comment_times_follow_new_replies() {
+        let mut data = thread();
+        assert_eq!(data.comment_times(100, &[1]).len(), 3);
+        data.set_comment(3, comment(3, 150, &[4]));
+        data.set_comment(4, comment(4, 250, &[]));
+        assert_eq!(data.comment_times(100, &[1]), [50, 80, 150, 250]);
+        assert_eq!(data.thread_size(&[1]), (4, 4));
+    }
+
+    #[test]
+    fn parses_bare_ids() {
+        assert_eq!(parse_item_id("8863"), Some(8863));
+        assert_eq!(parse_item_id("  8863\n"), Some(8863));
+    }
+
+    #[test]
+    fn parses_item_links() {
+        assert_eq!(
+            parse_item_id("https://news.ycombinator.com/item?id=8863"),
+            Some(8863)
+        );
+        assert_eq!(
+            parse_item_id("https://news.ycombinator.com/item?id=8863&p=2#8870"),
+            Some(8863)
+        );
+        assert_eq!(
+            parse_item_id("https://news.ycombinator.com/item?goto=news&id=8863"),
+            Some(8863)
+        );
+        assert_eq!(
+            parse_item_id("news.ycombinator.com/item?id=8863"),
+            Some(8863)
+        );
+        assert_eq!(parse_item_id("item?id=8863"), Some(8863));
+    }
+
+    #[test]
+    fn rejects_junk() {
+        assert_eq!(parse_item_id(""), None);
+        assert_eq!(parse_item_id("hello world"), None);
+        assert_eq!(parse_item_id("-5"), None);
+        assert_eq!(parse_item_id("https://example.com/item?id=8863"), None);
+        assert_eq!(
+            parse_item_id("https://news.ycombinator.com/user?id=pg"),
+            None
+        );
+        assert_eq!(
+            parse_item_id("https://news.ycombinator.com/item?id=abc"),
+            None
+        );
+        assert_eq!(parse_item_id("https://news.ycombinator.com/item"), None);
     }
 }
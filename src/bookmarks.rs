@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+/// The file bookmarks are kept in, inside the config directory.
+pub const DATA_FILE: &str = "bookmarks.toml";
+
+/// A saved story or comment. Enough of the item is copied in to list it without a connection.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Bookmark {
+    pub id: u32,
+    /// When it was saved, in seconds since the epoch.
+    pub saved: u64,
+    pub tags: Vec<String>,
+    pub by: Option<String>,
+    pub time: u64,
+    /// Only stories have titles.
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub text: Option<String>,
+}
+
+/// Bookmarks are stored locally and have nothing to do with favorites on the site.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Bookmarks {
+    bookmarks: Vec<Bookmark>,
+}
+
+impl Bookmarks {
+    pub fn contains(&self, id: u32) -> bool {
+        self.bookmarks.iter().any(|b| b.id == id)
+    }
+
+    /// Saves `bookmark`, or removes it if its item was already saved.
+    pub fn toggle(&mut self, bookmark: Bookmark) {
+        if self.contains(bookmark.id) {
+            self.remove(bookmark.id);
+        } else {
+            self.bookmarks.push(bookmark);
+        }
+    }
+
+    pub fn remove(&mut self, id: u32) {
+        self.bookmarks.retain(|b| b.id != id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.bookmarks.len()
+    }
+
+    pub fn get_mut(&mut self, id: u32) -> Option<&mut Bookmark> {
+        self.bookmarks.iter_mut().find(|b| b.id == id)
+    }
+
+    /// Bookmarks carrying `tag` (or all of them), most recently saved first unless `oldest_first`.
+    pub fn sorted(&self, tag: Option<&str>, oldest_first: bool) -> Vec<Bookmark> {
+        let mut bookmarks: Vec<Bookmark> = self
+            .bookmarks
+            .iter()
+            .filter(|b| tag.map_or(true, |tag| b.tags.iter().any(|t| t == tag)))
+            .cloned()
+            .collect();
+        bookmarks.sort_by_key(|b| b.saved);
+        if !oldest_first {
+            bookmarks.reverse();
+        }
+        bookmarks
+    }
+
+    /// Every tag in use, alphabetically.
+    pub fn tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = self
+            .bookmarks
+            .iter()
+            .flat_map(|b| b.tags.iter().cloned())
+            .collect();
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+}
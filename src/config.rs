@@ -0,0 +1,65 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+/// Where a user-editable config file lives, e.g. `~/.config/y-reader/keybindings.toml`.
+pub fn path(name: &str) -> Option<PathBuf> {
+    directories_next::ProjectDirs::from("", "", "y-reader").map(|dirs| dirs.config_dir().join(name))
+}
+
+/// Reads a TOML config file, returning `None` if the user hasn't created one.
+pub fn load<T: DeserializeOwned>(name: &str) -> Result<Option<T>, Box<dyn Error>> {
+    let path = match path(name) {
+        Some(path) if path.exists() => path,
+        _ => return Ok(None),
+    };
+
+    let text = fs::read_to_string(&path)?;
+    Ok(Some(toml::from_str(&text)?))
+}
+
+pub fn save<T: Serialize>(name: &str, value: &T) -> Result<(), Box<dyn Error>> {
+    let path = path(name).ok_or("no config directory for this platform")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(path, toml::to_string_pretty(value)?)?;
+    Ok(())
+}
+
+/// Stores a map with non-string keys, such as item ids, as a TOML table: TOML only allows
+/// string keys. Use with `#[serde(with = "config::string_keys")]`.
+pub mod string_keys {
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::{BTreeMap, HashMap};
+    use std::fmt::Display;
+    use std::hash::Hash;
+    use std::str::FromStr;
+
+    pub fn serialize<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: ToString,
+        V: Serialize,
+        S: Serializer,
+    {
+        map.iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect::<BTreeMap<_, _>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+    where
+        K: FromStr + Eq + Hash,
+        K::Err: Display,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        BTreeMap::<String, V>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(key, value)| Ok((key.parse().map_err(de::Error::custom)?, value)))
+            .collect()
+    }
+}
@@ -0,0 +1,389 @@
+use crate::app::BASE_URL;
+use crate::html::{self, Span};
+use chrono::{TimeZone, Utc};
+use serde::Serialize;
+use std::error::Error;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+/// A story and its comment tree.
+#[derive(Serialize)]
+pub struct Thread {
+    pub id: u32,
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub by: Option<String>,
+    /// Seconds since the epoch.
+    pub time: u64,
+    pub score: Option<u32>,
+    /// HTML, as returned by the API.
+    pub text: Option<String>,
+    pub comments: Vec<Comment>,
+}
+
+#[derive(Serialize)]
+pub struct Comment {
+    pub id: u32,
+    pub by: Option<String>,
+    pub time: u64,
+    pub text: Option<String>,
+    pub deleted: bool,
+    pub dead: bool,
+    pub replies: Vec<Comment>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Markdown,
+    Html,
+    Json,
+}
+
+impl Format {
+    pub const ALL: [Format; 3] = [Format::Markdown, Format::Html, Format::Json];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Markdown => "Markdown",
+            Format::Html => "HTML",
+            Format::Json => "JSON",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Markdown => "md",
+            Format::Html => "html",
+            Format::Json => "json",
+        }
+    }
+}
+
+impl Thread {
+    pub fn comment_count(&self) -> usize {
+        fn count(comments: &[Comment]) -> usize {
+            comments.iter().map(|c| 1 + count(&c.replies)).sum()
+        }
+        count(&self.comments)
+    }
+
+    pub fn render(&self, format: Format) -> Result<String, Box<dyn Error>> {
+        Ok(match format {
+            Format::Markdown => self.to_markdown(),
+            Format::Html => self.to_html(),
+            Format::Json => serde_json::to_string_pretty(self)?,
+        })
+    }
+
+    /// Writes the thread to the downloads folder (or home, failing that), returning the path.
+    pub fn save(&self, format: Format) -> Result<PathBuf, Box<dyn Error>> {
+        let dirs = directories_next::UserDirs::new().ok_or("no home directory")?;
+        let dir = dirs.download_dir().unwrap_or_else(|| dirs.home_dir());
+        let path = dir.join(format!("hn-{}.{}", self.id, format.extension()));
+        fs::write(&path, self.render(format)?)?;
+        Ok(path)
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let title = self.title.as_deref().unwrap_or_default();
+        let _ = writeln!(out, "# {}\n", title);
+        if let Some(url) = &self.url {
+            let _ = writeln!(out, "<{}>\n", url);
+        }
+
+        let mut meta = Vec::new();
+        if let Some(score) = self.score {
+            meta.push(format!("{} points", score));
+        }
+        if let Some(by) = &self.by {
+            meta.push(format!("by {}", by));
+        }
+        meta.push(timestamp(self.time));
+        meta.push(format!("[discussion]({})", item_url(self.id)));
+        let _ = writeln!(out, "*{}*\n", meta.join(" · "));
+
+        if let Some(text) = &self.text {
+            markdown_paragraphs(&mut out, text, "");
+        }
+        out.push_str("---\n\n");
+        markdown_comments(&mut out, &self.comments, 0);
+
+        out
+    }
+
+    fn to_html(&self) -> String {
+        let mut out = String::new();
+        let title = self.title.as_deref().unwrap_or_default();
+        let _ = write!(
+            out,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+             <style>\n\
+             body {{ font-family: sans-serif; max-width: 50em; margin: 2em auto; line-height: 1.4; }}\n\
+             .meta {{ color: #666; font-size: 0.9em; }}\n\
+             .comment {{ margin: 0.75em 0 0.75em 0.5em; padding-left: 0.75em; border-left: 2px solid #ddd; }}\n\
+             .dead > p {{ color: #999; }}\n\
+             </style>\n</head>\n<body>\n",
+            escape(title)
+        );
+
+        match &self.url {
+            Some(url) => {
+                let _ = writeln!(
+                    out,
+                    "<h1><a href=\"{}\">{}</a></h1>",
+                    attribute(url),
+                    escape(title)
+                );
+            }
+            None => {
+                let _ = writeln!(out, "<h1>{}</h1>", escape(title));
+            }
+        }
+        let mut meta = Vec::new();
+        if let Some(score) = self.score {
+            meta.push(format!("{} points", score));
+        }
+        if let Some(by) = &self.by {
+            meta.push(format!("by {}", escape(by)));
+        }
+        meta.push(format!(
+            "<a href=\"{}\">{}</a>",
+            item_url(self.id),
+            timestamp(self.time)
+        ));
+        let _ = writeln!(out, "<p class=\"meta\">{}</p>", meta.join(" · "));
+        if let Some(text) = &self.text {
+            html_paragraphs(&mut out, text);
+        }
+        out.push_str("<hr>\n");
+        html_comments(&mut out, &self.comments);
+
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+/// Comments as nested list items, their paragraphs indented to stay inside the item.
+fn markdown_comments(out: &mut String, comments: &[Comment], depth: usize) {
+    let indent = "  ".repeat(depth);
+    for c in comments {
+        let _ = writeln!(
+            out,
+            "{}- **{}** · [{}]({}){}\n",
+            indent,
+            c.by.as_deref().unwrap_or("[unknown]"),
+            timestamp(c.time),
+            item_url(c.id),
+            if c.dead { " · *[dead]*" } else { "" }
+        );
+        let body_indent = format!("{}  ", indent);
+        match &c.text {
+            _ if c.deleted => {
+                let _ = writeln!(out, "{}*[deleted]*\n", body_indent);
+            }
+            Some(text) => markdown_paragraphs(out, text, &body_indent),
+            None => {}
+        }
+        markdown_comments(out, &c.replies, depth + 1);
+    }
+}
+
+fn html_comments(out: &mut String, comments: &[Comment]) {
+    for c in comments {
+        let _ = writeln!(
+            out,
+            "<div class=\"comment{}\" id=\"{}\">\n<p class=\"meta\"><b>{}</b> · <a href=\"{}\">{}</a>{}</p>",
+            if c.dead { " dead" } else { "" },
+            c.id,
+            escape(c.by.as_deref().unwrap_or("[unknown]")),
+            item_url(c.id),
+            timestamp(c.time),
+            if c.dead { " · <i>[dead]</i>" } else { "" }
+        );
+        match &c.text {
+            _ if c.deleted => out.push_str("<p><i>[deleted]</i></p>\n"),
+            Some(text) => html_paragraphs(out, text),
+            None => {}
+        }
+        html_comments(out, &c.replies);
+        out.push_str("</div>\n");
+    }
+}
+
+fn markdown_paragraphs(out: &mut String, text: &str, indent: &str) {
+    for paragraph in html::parse(text) {
+        let line: String = paragraph
+            .iter()
+            .map(|span| match span {
+                Span::Text(text) => text.replace('\n', &format!("\n{}", indent)),
+                Span::Link { url, label } if url == label => format!("<{}>", url),
+                Span::Link { url, label } => format!("[{}]({})", label, url),
+            })
+            .collect();
+        let _ = writeln!(out, "{}{}\n", indent, line);
+    }
+}
+
+fn html_paragraphs(out: &mut String, text: &str) {
+    for paragraph in html::parse(text) {
+        out.push_str("<p>");
+        for span in paragraph {
+            match span {
+                Span::Text(text) => out.push_str(&escape(&text)),
+                Span::Link { url, label } => {
+                    let _ = write!(
+                        out,
+                        "<a href=\"{}\">{}</a>",
+                        attribute(&url),
+                        escape(&label)
+                    );
+                }
+            }
+        }
+        out.push_str("</p>\n");
+    }
+}
+
+fn escape(text: &str) -> String {
+    html_escape::encode_text(text).to_string()
+}
+
+fn attribute(text: &str) -> String {
+    html_escape::encode_double_quoted_attribute(text).to_string()
+}
+
+fn item_url(id: u32) -> String {
+    format!("{}/item?id={}", BASE_URL, id)
+}
+
+/// Formats seconds since the epoch as a UTC date and time, e.g. `2021-12-30 14:05 UTC`.
+fn timestamp(secs: u64) -> String {
+    Utc.timestamp_opt(secs as i64, 0)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comment(id: u32, by: &str, text: &str, replies: Vec<Comment>) -> Comment {
+        Comment {
+            id,
+            by: Some(by.to_string()),
+            time: 1_640_872_800,
+            text: Some(text.to_string()),
+            deleted: false,
+            dead: false,
+            replies,
+        }
+    }
+
+    fn thread() -> Thread {
+        let mut dead = comment(4, "troll", "Flagged", Vec::new());
+        dead.dead = true;
+        let deleted = Comment {
+            deleted: true,
+            text: None,
+            by: None,
+            ..comment(5, "", "", Vec::new())
+        };
+        Thread {
+            id: 1,
+            title: Some("Ask HN: <Tags> & things".to_string()),
+            url: None,
+            by: Some("asker".to_string()),
+            time: 1_640_872_800,
+            score: Some(42),
+            text: Some("Question?<p>Second paragraph".to_string()),
+            comments: vec![
+                comment(
+                    2,
+                    "alice",
+                    "See <a href=\"https://example.com/a?b=1&amp;c=2\">this</a>",
+                    vec![comment(3, "bob", "Agreed", Vec::new())],
+                ),
+                dead,
+                deleted,
+            ],
+        }
+    }
+
+    #[test]
+    fn counts_every_comment() {
+        assert_eq!(thread().comment_count(), 4);
+    }
+
+    #[test]
+    fn formats_timestamps_in_utc() {
+        assert_eq!(timestamp(1_640_872_800), "2021-12-30 14:00 UTC");
+    }
+
+    #[test]
+    fn markdown_nests_replies_and_marks_dead_and_deleted() {
+        let markdown = thread().render(Format::Markdown).unwrap();
+        let expected = "\
+# Ask HN: <Tags> & things
+
+*42 points · by asker · 2021-12-30 14:00 UTC · [discussion](https://news.ycombinator.com/item?id=1)*
+
+Question?
+
+Second paragraph
+
+---
+
+- **alice** · [2021-12-30 14:00 UTC](https://news.ycombinator.com/item?id=2)
+
+  See [this](https://example.com/a?b=1&c=2)
+
+  - **bob** · [2021-12-30 14:00 UTC](https://news.ycombinator.com/item?id=3)
+
+    Agreed
+
+- **troll** · [2021-12-30 14:00 UTC](https://news.ycombinator.com/item?id=4) · *[dead]*
+
+  Flagged
+
+- **[unknown]** · [2021-12-30 14:00 UTC](https://news.ycombinator.com/item?id=5)
+
+  *[deleted]*
+
+";
+        assert_eq!(markdown, expected);
+    }
+
+    #[test]
+    fn html_escapes_text_and_marks_dead_and_deleted() {
+        let html = thread().render(Format::Html).unwrap();
+        assert!(html.contains("<title>Ask HN: &lt;Tags&gt; &amp; things</title>"));
+        assert!(html.contains("<p>Question?</p>\n<p>Second paragraph</p>\n"));
+        assert!(
+            html.contains("<p>See <a href=\"https://example.com/a?b=1&amp;c=2\">this</a></p>\n")
+        );
+        assert!(html.contains("<div class=\"comment dead\" id=\"4\">"));
+        assert!(html.contains(" · <i>[dead]</i></p>"));
+        assert!(html.contains("<p><i>[deleted]</i></p>"));
+        // Replies sit inside their parent's block.
+        let alice = html.find("id=\"2\"").unwrap();
+        let bob = html.find("id=\"3\"").unwrap();
+        let troll = html.find("id=\"4\"").unwrap();
+        assert!(alice < bob && bob < troll);
+        assert_eq!(html[alice..troll].matches("</div>").count(), 2);
+        assert!(html.ends_with("</body>\n</html>\n"));
+    }
+
+    #[test]
+    fn json_keeps_the_tree() {
+        let json: serde_json::Value =
+            serde_json::from_str(&thread().render(Format::Json).unwrap()).unwrap();
+        assert_eq!(json["id"], 1);
+        assert_eq!(json["comments"][0]["by"], "alice");
+        assert_eq!(json["comments"][0]["replies"][0]["text"], "Agreed");
+        assert_eq!(json["comments"][1]["dead"], true);
+        assert_eq!(json["comments"][2]["deleted"], true);
+    }
+}
@@ -0,0 +1,281 @@
+use lazy_static::lazy_static;
+use reqwest::blocking::Client;
+use reqwest::header::{COOKIE, SET_COOKIE};
+use reqwest::redirect::Policy;
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::error::Error;
+use url::Url;
+
+/// The file imported lists are kept in, inside the config directory.
+pub const DATA_FILE: &str = "favorites.toml";
+
+/// Imports stop after this many pages, in case a page's "More" link loops.
+const MAX_PAGES: usize = 50;
+/// The only host the importer will send a password to.
+const SITE_HOST: &str = "news.ycombinator.com";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum List {
+    Favorites,
+    Upvoted,
+}
+
+impl List {
+    pub fn name(self) -> &'static str {
+        match self {
+            List::Favorites => "Favorites",
+            List::Upvoted => "Upvoted",
+        }
+    }
+
+    fn path(self) -> &'static str {
+        match self {
+            List::Favorites => "favorites",
+            List::Upvoted => "upvoted",
+        }
+    }
+}
+
+/// The story ids of both lists, newest first, as last imported.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Imported {
+    pub favorites: Vec<u32>,
+    pub upvoted: Vec<u32>,
+}
+
+/// One page of a listing on the site.
+#[derive(Debug, PartialEq)]
+pub struct Page {
+    pub ids: Vec<u32>,
+    /// The "More" link, relative to the site root.
+    pub next: Option<String>,
+}
+
+/// Pulls the story ids and the link to the next page out of a listing page's HTML.
+pub fn parse_page(html: &str) -> Page {
+    lazy_static! {
+        static ref STORY: Selector = Selector::parse("tr.athing").unwrap();
+        static ref MORE: Selector = Selector::parse("a.morelink").unwrap();
+    }
+
+    let document = Html::parse_document(html);
+    Page {
+        ids: document
+            .select(&STORY)
+            .filter_map(|row| row.value().attr("id")?.parse().ok())
+            .collect(),
+        next: document
+            .select(&MORE)
+            .next()
+            .and_then(|link| link.value().attr("href"))
+            .map(|href| href.to_string()),
+    }
+}
+
+/// Scrapes favorites and upvoted lists page by page. The site is passed in so the importer
+/// can be pointed at saved pages served locally.
+pub struct Importer {
+    base: Url,
+    client: Client,
+}
+
+impl Importer {
+    pub fn new(base_url: &str) -> Result<Self, Box<dyn Error>> {
+        // Without the trailing slash, joining would replace the last path segment.
+        let mut base = Url::parse(base_url)?;
+        if !base.path().ends_with('/') {
+            base.set_path(&format!("{}/", base.path()));
+        }
+
+        // A successful login answers with a redirect, which we need to see rather than follow.
+        let client = Client::builder().redirect(Policy::none()).build()?;
+        Ok(Self { base, client })
+    }
+
+    /// Signs in, returning the session cookie needed to read the upvoted list.
+    pub fn login(&self, username: &str, password: &str) -> Result<String, Box<dyn Error>> {
+        if self.base.scheme() != "https" || self.base.host_str() != Some(SITE_HOST) {
+            return Err(format!("won't sign in anywhere but https://{}", SITE_HOST).into());
+        }
+
+        let response = self
+            .client
+            .post(self.base.join("login")?)
+            .form(&[("acct", username), ("pw", password), ("goto", "news")])
+            .send()?;
+
+        response
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|cookie| cookie.to_str().ok())
+            .filter_map(|cookie| cookie.split(';').next())
+            .find(|pair| pair.starts_with("user="))
+            .map(|pair| pair.to_string())
+            .ok_or_else(|| "sign in failed; check your username and password".into())
+    }
+
+    /// Walks `list` from the newest page, returning the ids not in `known`. Since lists are
+    /// newest first, it stops at the first page that reaches a story we already have.
+    pub fn fetch_new(
+        &self,
+        list: List,
+        username: &str,
+        cookie: Option<&str>,
+        known: &HashSet<u32>,
+    ) -> Result<Vec<u32>, Box<dyn Error>> {
+        let mut url = self.base.join(list.path())?;
+        url.query_pairs_mut().append_pair("id", username);
+
+        let mut ids = Vec::new();
+        for _ in 0..MAX_PAGES {
+            let mut request = self.client.get(url.clone());
+            if let Some(cookie) = cookie {
+                request = request.header(COOKIE, cookie);
+            }
+            let response = request.send()?.error_for_status()?;
+            let page = parse_page(&response.text()?);
+
+            let fresh: Vec<u32> = page
+                .ids
+                .iter()
+                .copied()
+                .take_while(|id| !known.contains(id))
+                .collect();
+            let caught_up = fresh.len() < page.ids.len();
+            ids.extend(fresh);
+            if caught_up {
+                break;
+            }
+            match page.next {
+                Some(next) => url = self.base.join(&next)?,
+                None => break,
+            }
+        }
+
+        Ok(ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    const PAGE_1: &str = include_str!("../tests/fixtures/favorites-page1.html");
+    const PAGE_2: &str = include_str!("../tests/fixtures/favorites-page2.html");
+
+    /// Serves the saved pages on a local port, picking the page with `page_for` from each
+    /// request's path. Returns the server's address and a count of the requests it answered.
+    fn serve(page_for: fn(&str) -> &'static str) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let count = Arc::clone(&requests);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                // Skip the headers; none of the requests have a body.
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+
+                count.fetch_add(1, Ordering::SeqCst);
+                let path = request_line.split(' ').nth(1).unwrap_or_default();
+                let body = page_for(path);
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+        (address, requests)
+    }
+
+    fn two_pages(path: &str) -> &'static str {
+        if path.ends_with("p=2") {
+            PAGE_2
+        } else {
+            PAGE_1
+        }
+    }
+
+    #[test]
+    fn parses_ids_and_more_link() {
+        assert_eq!(
+            parse_page(PAGE_1),
+            Page {
+                ids: vec![8863, 121003, 1],
+                next: Some("favorites?id=pg&p=2".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn last_page_has_no_more_link() {
+        assert_eq!(
+            parse_page(PAGE_2),
+            Page {
+                ids: vec![363, 17],
+                next: None,
+            }
+        );
+    }
+
+    #[test]
+    fn follows_more_links() {
+        let (address, requests) = serve(two_pages);
+        let importer = Importer::new(&address).unwrap();
+        let ids = importer
+            .fetch_new(List::Favorites, "pg", None, &HashSet::new())
+            .unwrap();
+        assert_eq!(ids, vec![8863, 121003, 1, 363, 17]);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn stops_at_a_known_story() {
+        let (address, requests) = serve(two_pages);
+        let importer = Importer::new(&address).unwrap();
+        let known = [121003, 363].into_iter().collect();
+        let ids = importer
+            .fetch_new(List::Favorites, "pg", None, &known)
+            .unwrap();
+        assert_eq!(ids, vec![8863]);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn gives_up_after_max_pages() {
+        // Every page links on to another, as if the "More" link looped.
+        let (address, requests) = serve(|_| PAGE_1);
+        let importer = Importer::new(&address).unwrap();
+        let ids = importer
+            .fetch_new(List::Favorites, "pg", None, &HashSet::new())
+            .unwrap();
+        assert_eq!(ids.len(), MAX_PAGES * 3);
+        assert_eq!(requests.load(Ordering::SeqCst), MAX_PAGES);
+    }
+
+    #[test]
+    fn only_signs_in_to_the_site() {
+        let (address, requests) = serve(two_pages);
+        let importer = Importer::new(&address).unwrap();
+        assert!(importer.login("pg", "hunter2").is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 0);
+    }
+}
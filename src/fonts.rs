@@ -0,0 +1,65 @@
+use eframe::egui::{FontData, FontDefinitions, FontFamily};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Fonts covering Chinese, Japanese and Korean, looked for in this order. Only the first one
+/// found is loaded, since each is tens of megabytes.
+const CJK_FALLBACKS: [&str; 10] = [
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/truetype/wqy/wqy-microhei.ttc",
+    "/usr/share/fonts/truetype/droid/DroidSansFallbackFull.ttf",
+    "/System/Library/Fonts/Hiragino Sans GB.ttc",
+    "/System/Library/Fonts/Supplemental/Arial Unicode.ttf",
+    "/Library/Fonts/Arial Unicode.ttf",
+    "C:\\Windows\\Fonts\\msyh.ttc",
+    "C:\\Windows\\Fonts\\msgothic.ttc",
+];
+
+/// Outline emoji and symbol fonts, for whatever egui's bundled emoji font lacks. Color-only
+/// fonts such as Apple Color Emoji can't be drawn by egui, so they aren't listed.
+const EMOJI_FALLBACKS: [&str; 4] = [
+    "/usr/share/fonts/truetype/noto/NotoEmoji-Regular.ttf",
+    "/usr/share/fonts/truetype/ancient-scripts/Symbola_hint.ttf",
+    "C:\\Windows\\Fonts\\seguiemj.ttf",
+    "C:\\Windows\\Fonts\\seguisym.ttf",
+];
+
+/// Reads a font file, checking that it parses so egui won't panic on it later.
+pub fn read(path: &Path) -> Result<FontData, Box<dyn Error>> {
+    let bytes = fs::read(path)?;
+    ab_glyph::FontRef::try_from_slice(&bytes)?;
+    Ok(FontData::from_owned(bytes))
+}
+
+/// The first CJK font and the first emoji font installed, if any.
+pub fn system_fallbacks() -> Vec<&'static Path> {
+    [&CJK_FALLBACKS[..], &EMOJI_FALLBACKS[..]]
+        .iter()
+        .filter_map(|paths| paths.iter().map(Path::new).find(|path| path.exists()))
+        .collect()
+}
+
+/// Makes `name` the first font tried for `family`, ahead of egui's own.
+pub fn set_primary(fonts: &mut FontDefinitions, family: FontFamily, name: &str, data: FontData) {
+    fonts.font_data.insert(name.to_string(), data);
+    fonts
+        .fonts_for_family
+        .entry(family)
+        .or_default()
+        .insert(0, name.to_string());
+}
+
+/// Adds `name` as the last resort for both families.
+pub fn add_fallback(fonts: &mut FontDefinitions, name: &str, data: FontData) {
+    fonts.font_data.insert(name.to_string(), data);
+    for family in [FontFamily::Proportional, FontFamily::Monospace] {
+        fonts
+            .fonts_for_family
+            .entry(family)
+            .or_default()
+            .push(name.to_string());
+    }
+}
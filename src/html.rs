@@ -0,0 +1,75 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// A run of text inside a paragraph of item HTML.
+pub enum Span {
+    Text(String),
+    Link { url: String, label: String },
+}
+
+/// Splits the HTML of a comment or story text into paragraphs of plain text and links.
+/// Other markup is dropped and entities are decoded.
+pub fn parse(text: &str) -> Vec<Vec<Span>> {
+    lazy_static! {
+        static ref LINK: Regex =
+            Regex::new(r#"<a\s+href=(?:"([^"]+)"|'([^']+)').*?>(.*?)</a>"#).unwrap();
+    }
+
+    text.split("<p>")
+        .map(|paragraph| {
+            let mut spans = Vec::new();
+            let mut end = 0;
+            for link in LINK.captures_iter(paragraph) {
+                let whole = link.get(0).unwrap();
+                push_text(&mut spans, &paragraph[end..whole.start()]);
+
+                let url = decode(link.get(1).or_else(|| link.get(2)).unwrap().as_str());
+                let label = link.get(3).map(|l| decode(l.as_str())).unwrap_or_default();
+                spans.push(Span::Link {
+                    label: if label.is_empty() { url.clone() } else { label },
+                    url,
+                });
+                end = whole.end();
+            }
+            push_text(&mut spans, &paragraph[end..]);
+            spans
+        })
+        .filter(|spans| !spans.is_empty())
+        .collect()
+}
+
+/// The first line of item HTML as plain text, with links reduced to their labels.
+pub fn first_line(text: &str) -> String {
+    let paragraph: String = parse(text)
+        .into_iter()
+        .next()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|span| match span {
+            Span::Text(text) => text,
+            Span::Link { label, .. } => label,
+        })
+        .collect();
+    paragraph
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+fn push_text(spans: &mut Vec<Span>, html: &str) {
+    let text = decode(html);
+    if !text.is_empty() {
+        spans.push(Span::Text(text));
+    }
+}
+
+/// Strips tags, then decodes entities, so escaped angle brackets in the text survive.
+fn decode(html: &str) -> String {
+    lazy_static! {
+        static ref TAG: Regex = Regex::new(r"<[^>]*>").unwrap();
+    }
+
+    html_escape::decode_html_entities(&TAG.replace_all(html, "")).to_string()
+}
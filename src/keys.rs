@@ -0,0 +1,665 @@
+use eframe::egui::{self, Key};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+/// The file the keymap is read from and saved to, inside the config directory.
+pub const CONFIG_FILE: &str = "keybindings.toml";
+
+/// Everything that can be triggered from the keyboard.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Action {
+    SelectNext,
+    SelectPrevious,
+    NextTopLevel,
+    OpenLink,
+    OpenThread,
+    ToggleCollapse,
+    NextNew,
+    Back,
+    ShowTop,
+    ShowNew,
+    ShowShow,
+    ShowFavorites,
+    ShowSaved,
+    ToggleHelp,
+    CommandPalette,
+    ToggleTheme,
+    Refresh,
+    ShowSettings,
+    FocusSearch,
+    ShowAlerts,
+    ZoomIn,
+    ZoomOut,
+    ResetZoom,
+    Bookmark,
+    ExportThread,
+}
+
+impl Action {
+    pub const ALL: [Action; 25] = [
+        Action::SelectNext,
+        Action::SelectPrevious,
+        Action::NextTopLevel,
+        Action::OpenLink,
+        Action::OpenThread,
+        Action::ToggleCollapse,
+        Action::NextNew,
+        Action::Back,
+        Action::ShowTop,
+        Action::ShowNew,
+        Action::ShowShow,
+        Action::ShowFavorites,
+        Action::ShowSaved,
+        Action::ToggleHelp,
+        Action::CommandPalette,
+        Action::ToggleTheme,
+        Action::Refresh,
+        Action::ShowSettings,
+        Action::FocusSearch,
+        Action::ShowAlerts,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::ResetZoom,
+        Action::Bookmark,
+        Action::ExportThread,
+    ];
+
+    /// The name used for the action in the config file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::SelectNext => "select_next",
+            Action::SelectPrevious => "select_previous",
+            Action::NextTopLevel => "next_top_level",
+            Action::OpenLink => "open_link",
+            Action::OpenThread => "open_thread",
+            Action::ToggleCollapse => "toggle_collapse",
+            Action::NextNew => "next_new",
+            Action::Back => "back",
+            Action::ShowTop => "show_top",
+            Action::ShowNew => "show_new",
+            Action::ShowShow => "show_show",
+            Action::ShowFavorites => "show_favorites",
+            Action::ShowSaved => "show_saved",
+            Action::ToggleHelp => "toggle_help",
+            Action::CommandPalette => "command_palette",
+            Action::ToggleTheme => "toggle_theme",
+            Action::Refresh => "refresh",
+            Action::ShowSettings => "show_settings",
+            Action::FocusSearch => "focus_search",
+            Action::ShowAlerts => "show_alerts",
+            Action::ZoomIn => "zoom_in",
+            Action::ZoomOut => "zoom_out",
+            Action::ResetZoom => "reset_zoom",
+            Action::Bookmark => "bookmark",
+            Action::ExportThread => "export_thread",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::SelectNext => "Select next item",
+            Action::SelectPrevious => "Select previous item",
+            Action::NextTopLevel => "Jump to next top-level comment",
+            Action::OpenLink => "Open link",
+            Action::OpenThread => "Open thread",
+            Action::ToggleCollapse => "Expand or collapse comments",
+            Action::NextNew => "Jump to next new comment",
+            Action::Back => "Go back",
+            Action::ShowTop => "Show Top",
+            Action::ShowNew => "Show New",
+            Action::ShowShow => "Show Show",
+            Action::ShowFavorites => "Show Favorites",
+            Action::ShowSaved => "Show Saved",
+            Action::ToggleHelp => "Show keyboard shortcuts",
+            Action::CommandPalette => "Show command palette",
+            Action::ToggleTheme => "Toggle dark/light theme",
+            Action::Refresh => "Refresh feeds now",
+            Action::ShowSettings => "Open settings",
+            Action::FocusSearch => "Filter loaded stories",
+            Action::ShowAlerts => "Show watch list alerts",
+            Action::ZoomIn => "Zoom in",
+            Action::ZoomOut => "Zoom out",
+            Action::ResetZoom => "Reset zoom",
+            Action::Bookmark => "Save or unsave the selected item",
+            Action::ExportThread => "Export thread",
+        }
+    }
+}
+
+/// Every key egui reports, with the name used for it in the config file.
+const KEY_NAMES: [(Key, &str); 62] = [
+    (Key::ArrowDown, "ArrowDown"),
+    (Key::ArrowLeft, "ArrowLeft"),
+    (Key::ArrowRight, "ArrowRight"),
+    (Key::ArrowUp, "ArrowUp"),
+    (Key::Escape, "Escape"),
+    (Key::Tab, "Tab"),
+    (Key::Backspace, "Backspace"),
+    (Key::Enter, "Enter"),
+    (Key::Space, "Space"),
+    (Key::Insert, "Insert"),
+    (Key::Delete, "Delete"),
+    (Key::Home, "Home"),
+    (Key::End, "End"),
+    (Key::PageUp, "PageUp"),
+    (Key::PageDown, "PageDown"),
+    (Key::Num0, "0"),
+    (Key::Num1, "1"),
+    (Key::Num2, "2"),
+    (Key::Num3, "3"),
+    (Key::Num4, "4"),
+    (Key::Num5, "5"),
+    (Key::Num6, "6"),
+    (Key::Num7, "7"),
+    (Key::Num8, "8"),
+    (Key::Num9, "9"),
+    (Key::A, "A"),
+    (Key::B, "B"),
+    (Key::C, "C"),
+    (Key::D, "D"),
+    (Key::E, "E"),
+    (Key::F, "F"),
+    (Key::G, "G"),
+    (Key::H, "H"),
+    (Key::I, "I"),
+    (Key::J, "J"),
+    (Key::K, "K"),
+    (Key::L, "L"),
+    (Key::M, "M"),
+    (Key::N, "N"),
+    (Key::O, "O"),
+    (Key::P, "P"),
+    (Key::Q, "Q"),
+    (Key::R, "R"),
+    (Key::S, "S"),
+    (Key::T, "T"),
+    (Key::U, "U"),
+    (Key::V, "V"),
+    (Key::W, "W"),
+    (Key::X, "X"),
+    (Key::Y, "Y"),
+    (Key::Z, "Z"),
+    // Aliases, accepted when reading the config file.
+    (Key::ArrowDown, "Down"),
+    (Key::ArrowLeft, "Left"),
+    (Key::ArrowRight, "Right"),
+    (Key::ArrowUp, "Up"),
+    (Key::Escape, "Esc"),
+    (Key::Enter, "Return"),
+    (Key::Delete, "Del"),
+    (Key::Insert, "Ins"),
+    (Key::PageUp, "PgUp"),
+    (Key::PageDown, "PgDown"),
+    (Key::Backspace, "Back"),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum Binding {
+    Key {
+        key: Key,
+        ctrl: bool,
+        shift: bool,
+    },
+    /// A typed character, for symbols like `?` that egui has no `Key` for.
+    Char(char),
+    /// A character typed with Ctrl held, like `Ctrl++`. macOS doesn't report these and
+    /// Windows drops some, so an action bound to one needs a `Key` binding as well.
+    CtrlChar(char),
+}
+
+impl Binding {
+    const fn key(key: Key) -> Self {
+        Binding::Key {
+            key,
+            ctrl: false,
+            shift: false,
+        }
+    }
+
+    const fn ctrl(key: Key) -> Self {
+        Binding::Key {
+            key,
+            ctrl: true,
+            shift: false,
+        }
+    }
+
+    fn typed(c: char, ctrl: bool) -> Self {
+        if ctrl {
+            Binding::CtrlChar(c)
+        } else {
+            Binding::Char(c)
+        }
+    }
+
+    /// The first key pressed this frame, if any, for recording a new binding.
+    fn pressed(input: &egui::InputState) -> Option<Self> {
+        input.events.iter().find_map(|event| match event {
+            egui::Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+            } if !modifiers.alt => Some(Binding::Key {
+                key: *key,
+                ctrl: modifiers.command,
+                shift: modifiers.shift,
+            }),
+            egui::Event::Text(text) => {
+                let mut chars = text.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if !c.is_alphanumeric() && !c.is_whitespace() => {
+                        Some(Binding::typed(c, input.modifiers.command))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Binding::Key { key, ctrl, shift } => {
+                if *ctrl {
+                    write!(f, "Ctrl+")?;
+                }
+                if *shift {
+                    write!(f, "Shift+")?;
+                }
+                let name = KEY_NAMES
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, name)| *name)
+                    .unwrap_or_default();
+                write!(f, "{}", name)
+            }
+            Binding::Char(c) => write!(f, "{}", c),
+            Binding::CtrlChar(c) => write!(f, "Ctrl+{}", c),
+        }
+    }
+}
+
+impl FromStr for Binding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut ctrl = false;
+        let mut shift = false;
+        let mut rest = s.trim();
+
+        // A lone "+" is a character binding, not an empty chord.
+        while let Some((modifier, tail)) = rest.split_once('+').filter(|(_, t)| !t.is_empty()) {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "cmd" | "command" => ctrl = true,
+                "shift" => shift = true,
+                _ => return Err(format!("unknown modifier \"{}\" in \"{}\"", modifier, s)),
+            }
+            rest = tail;
+        }
+
+        if let Some((key, _)) = KEY_NAMES
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(rest))
+        {
+            return Ok(Binding::Key {
+                key: *key,
+                ctrl,
+                shift,
+            });
+        }
+
+        let mut chars = rest.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if !shift => Ok(Binding::typed(c, ctrl)),
+            _ => Err(format!("unknown key \"{}\"", s)),
+        }
+    }
+}
+
+impl From<Binding> for String {
+    fn from(binding: Binding) -> Self {
+        binding.to_string()
+    }
+}
+
+impl TryFrom<String> for Binding {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Preset {
+    Vim,
+    Arrows,
+}
+
+/// Which keys trigger which actions. Each action may have any number of bindings.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(
+    into = "BTreeMap<String, Vec<Binding>>",
+    try_from = "BTreeMap<String, Vec<Binding>>"
+)]
+pub struct Keymap {
+    bindings: BTreeMap<Action, Vec<Binding>>,
+}
+
+impl From<Keymap> for BTreeMap<String, Vec<Binding>> {
+    fn from(keymap: Keymap) -> Self {
+        keymap
+            .bindings
+            .into_iter()
+            .map(|(action, bindings)| (action.name().to_string(), bindings))
+            .collect()
+    }
+}
+
+/// Actions the file leaves out keep their default keys, so a file can rebind just a few.
+impl TryFrom<BTreeMap<String, Vec<Binding>>> for Keymap {
+    type Error = String;
+
+    fn try_from(map: BTreeMap<String, Vec<Binding>>) -> Result<Self, Self::Error> {
+        let mut keymap = Self::default();
+        for (name, keys) in map {
+            let action = Action::ALL
+                .iter()
+                .find(|action| action.name() == name)
+                .ok_or_else(|| format!("unknown action \"{}\"", name))?;
+            keymap.bindings.insert(*action, keys);
+        }
+        Ok(keymap)
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::preset(Preset::Vim)
+    }
+}
+
+impl Keymap {
+    pub fn preset(preset: Preset) -> Self {
+        let bindings: &[(Action, Binding)] = match preset {
+            Preset::Vim => &[
+                (Action::SelectNext, Binding::key(Key::J)),
+                (Action::SelectNext, Binding::key(Key::ArrowDown)),
+                (Action::SelectPrevious, Binding::key(Key::K)),
+                (Action::SelectPrevious, Binding::key(Key::ArrowUp)),
+                (Action::NextTopLevel, Binding::key(Key::N)),
+                (Action::OpenLink, Binding::key(Key::O)),
+                (Action::OpenThread, Binding::key(Key::C)),
+                (Action::ToggleCollapse, Binding::key(Key::Space)),
+                (Action::NextNew, Binding::key(Key::U)),
+                (Action::Back, Binding::key(Key::Backspace)),
+                (Action::ShowTop, Binding::key(Key::Num1)),
+                (Action::ShowNew, Binding::key(Key::Num2)),
+                (Action::ShowShow, Binding::key(Key::Num3)),
+                (Action::ShowFavorites, Binding::key(Key::Num4)),
+                (Action::ShowSaved, Binding::key(Key::Num5)),
+                (Action::ToggleHelp, Binding::Char('?')),
+                (Action::CommandPalette, Binding::ctrl(Key::K)),
+                (Action::FocusSearch, Binding::Char('/')),
+                (Action::ShowAlerts, Binding::Char('!')),
+                (Action::ZoomIn, Binding::CtrlChar('+')),
+                (Action::ZoomIn, Binding::CtrlChar('=')),
+                (Action::ZoomIn, Binding::ctrl(Key::PageUp)),
+                (Action::ZoomOut, Binding::CtrlChar('-')),
+                (Action::ZoomOut, Binding::ctrl(Key::PageDown)),
+                (Action::ResetZoom, Binding::ctrl(Key::Num0)),
+                (Action::Bookmark, Binding::key(Key::S)),
+                (Action::ExportThread, Binding::key(Key::E)),
+            ],
+            Preset::Arrows => &[
+                (Action::SelectNext, Binding::key(Key::ArrowDown)),
+                (Action::SelectPrevious, Binding::key(Key::ArrowUp)),
+                (Action::NextTopLevel, Binding::key(Key::PageDown)),
+                (Action::OpenLink, Binding::key(Key::Enter)),
+                (Action::OpenThread, Binding::key(Key::ArrowRight)),
+                (Action::ToggleCollapse, Binding::key(Key::Space)),
+                (Action::NextNew, Binding::ctrl(Key::ArrowDown)),
+                (Action::Back, Binding::key(Key::ArrowLeft)),
+                (Action::ShowTop, Binding::key(Key::Num1)),
+                (Action::ShowNew, Binding::key(Key::Num2)),
+                (Action::ShowShow, Binding::key(Key::Num3)),
+                (Action::ShowFavorites, Binding::key(Key::Num4)),
+                (Action::ShowSaved, Binding::key(Key::Num5)),
+                (Action::ToggleHelp, Binding::Char('?')),
+                (Action::CommandPalette, Binding::ctrl(Key::K)),
+                (Action::FocusSearch, Binding::Char('/')),
+                (Action::ShowAlerts, Binding::Char('!')),
+                (Action::ZoomIn, Binding::CtrlChar('+')),
+                (Action::ZoomIn, Binding::CtrlChar('=')),
+                (Action::ZoomIn, Binding::ctrl(Key::PageUp)),
+                (Action::ZoomOut, Binding::CtrlChar('-')),
+                (Action::ZoomOut, Binding::ctrl(Key::PageDown)),
+                (Action::ResetZoom, Binding::ctrl(Key::Num0)),
+                (Action::Bookmark, Binding::ctrl(Key::S)),
+                (Action::ExportThread, Binding::ctrl(Key::E)),
+            ],
+        };
+
+        let mut keymap = Self {
+            bindings: BTreeMap::new(),
+        };
+        for (action, binding) in bindings {
+            keymap.bindings.entry(*action).or_default().push(*binding);
+        }
+        keymap
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.bindings
+            .get(&action)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Bindings that are claimed by more than one action, and the actions claiming them. Only
+    /// the first action listed is triggered.
+    pub fn conflicts(&self) -> Vec<(Binding, Vec<Action>)> {
+        let mut conflicts: Vec<(Binding, Vec<Action>)> = Vec::new();
+        for (action, bindings) in &self.bindings {
+            for binding in bindings {
+                match conflicts.iter_mut().find(|(b, _)| b == binding) {
+                    Some((_, actions)) => actions.push(*action),
+                    None => conflicts.push((*binding, vec![*action])),
+                }
+            }
+        }
+        conflicts.retain(|(_, actions)| actions.len() > 1);
+        conflicts
+    }
+
+    /// Returns the actions whose keys were pressed this frame. While a text field has focus
+    /// only Ctrl chords count, so that typing doesn't trigger anything. A key bound to several
+    /// actions triggers the first of them.
+    pub fn pressed_actions(&self, ctx: &egui::CtxRef) -> Vec<Action> {
+        let typing = ctx.wants_keyboard_input();
+        let input = ctx.input();
+
+        let mut actions = Vec::new();
+        for event in &input.events {
+            let pressed = match event {
+                egui::Event::Key {
+                    key,
+                    pressed: true,
+                    modifiers,
+                } if !modifiers.alt => Binding::Key {
+                    key: *key,
+                    ctrl: modifiers.command,
+                    shift: modifiers.shift,
+                },
+                egui::Event::Text(text) if text.chars().count() == 1 => {
+                    Binding::typed(text.chars().next().unwrap(), input.modifiers.command)
+                }
+                _ => continue,
+            };
+            if typing
+                && !matches!(
+                    pressed,
+                    Binding::Key { ctrl: true, .. } | Binding::CtrlChar(_)
+                )
+            {
+                continue;
+            }
+
+            actions.extend(
+                self.bindings
+                    .iter()
+                    .find(|(_, bindings)| bindings.contains(&pressed))
+                    .map(|(action, _)| *action),
+            );
+        }
+        actions
+    }
+
+    /// The keymap editor shown in the settings window. `recording` holds the action that is
+    /// waiting for a key to be pressed. Returns whether the keymap was changed.
+    pub fn settings_ui(&mut self, ui: &mut egui::Ui, recording: &mut Option<Action>) -> bool {
+        let mut changed = false;
+
+        ui.horizontal(|ui| {
+            ui.label("Presets:");
+            if ui.button("Vim-style").clicked() {
+                *self = Self::preset(Preset::Vim);
+                changed = true;
+            }
+            if ui.button("Arrow keys").clicked() {
+                *self = Self::preset(Preset::Arrows);
+                changed = true;
+            }
+        });
+
+        if let Some(action) = *recording {
+            if let Some(binding) = Binding::pressed(ui.input()) {
+                if binding != Binding::key(Key::Escape) {
+                    let bindings = self.bindings.entry(action).or_default();
+                    if !bindings.contains(&binding) {
+                        bindings.push(binding);
+                        changed = true;
+                    }
+                }
+                *recording = None;
+            }
+        }
+
+        egui::Grid::new("keymap").striped(true).show(ui, |ui| {
+            for action in Action::ALL {
+                ui.label(action.description());
+                ui.horizontal(|ui| {
+                    let mut removed = None;
+                    for (i, binding) in self.bindings(action).iter().enumerate() {
+                        if ui
+                            .small_button(binding.to_string())
+                            .on_hover_text("Click to remove")
+                            .clicked()
+                        {
+                            removed = Some(i);
+                        }
+                    }
+                    if let Some(i) = removed {
+                        self.bindings.entry(action).or_default().remove(i);
+                        changed = true;
+                    }
+
+                    if *recording == Some(action) {
+                        ui.label(egui::RichText::new("Press a key… (Esc to cancel)").weak());
+                    } else if ui.small_button("+").on_hover_text("Add a key").clicked() {
+                        *recording = Some(action);
+                    }
+                });
+                ui.end_row();
+            }
+        });
+
+        for (binding, actions) in self.conflicts() {
+            let actions: Vec<&str> = actions.iter().map(|a| a.description()).collect();
+            ui.colored_label(
+                ui.visuals().selection.bg_fill,
+                format!(
+                    "{} is bound to: {}. Only the first will be used.",
+                    binding,
+                    actions.join(", ")
+                ),
+            );
+        }
+
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ctrl_chars() {
+        assert_eq!("Ctrl++".parse(), Ok(Binding::CtrlChar('+')));
+        assert_eq!("ctrl+=".parse(), Ok(Binding::CtrlChar('=')));
+        assert_eq!("+".parse(), Ok(Binding::Char('+')));
+        assert_eq!("?".parse(), Ok(Binding::Char('?')));
+    }
+
+    #[test]
+    fn parses_named_keys() {
+        assert_eq!("j".parse(), Ok(Binding::key(Key::J)));
+        assert_eq!("Cmd+K".parse(), Ok(Binding::ctrl(Key::K)));
+        assert_eq!("PgDown".parse(), Ok(Binding::key(Key::PageDown)));
+        assert_eq!(
+            "Ctrl+Shift+Up".parse(),
+            Ok(Binding::Key {
+                key: Key::ArrowUp,
+                ctrl: true,
+                shift: true,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        assert!("Hyper+J".parse::<Binding>().is_err());
+        assert!("F13".parse::<Binding>().is_err());
+        assert!("Shift+?".parse::<Binding>().is_err());
+        assert!(toml::from_str::<Keymap>("fly = [\"F\"]").is_err());
+    }
+
+    #[test]
+    fn displays_what_it_parses() {
+        for binding in ["Ctrl+PageUp", "Ctrl+-", "Shift+Space", "?"] {
+            assert_eq!(binding.parse::<Binding>().unwrap().to_string(), binding);
+        }
+    }
+
+    #[test]
+    fn merges_over_the_default_preset() {
+        let keymap: Keymap = toml::from_str("select_next = [\"Ctrl+N\"]").unwrap();
+        assert_eq!(keymap.bindings(Action::SelectNext), [Binding::ctrl(Key::N)]);
+        assert_eq!(
+            keymap.bindings(Action::SelectPrevious),
+            Keymap::default().bindings(Action::SelectPrevious)
+        );
+    }
+
+    #[test]
+    fn finds_conflicts() {
+        assert!(Keymap::preset(Preset::Vim).conflicts().is_empty());
+        assert!(Keymap::preset(Preset::Arrows).conflicts().is_empty());
+
+        let keymap: Keymap = toml::from_str("open_link = [\"J\"]").unwrap();
+        assert_eq!(
+            keymap.conflicts(),
+            [(
+                Binding::key(Key::J),
+                vec![Action::SelectNext, Action::OpenLink]
+            )]
+        );
+    }
+}
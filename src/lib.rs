@@ -3,6 +3,19 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
 mod app;
+mod bookmarks;
+mod config;
+mod export;
+mod favorites;
+mod fonts;
+mod html;
+mod keys;
+mod mute;
+mod palette;
+mod settings;
+mod theme;
+mod timestamp;
+mod watch;
 pub use app::YReader;
 
 // ----------------------------------------------------------------------------
@@ -13,13 +13,14 @@ fn main() {
     let (icon_width, icon_height) = icon.dimensions();
 
     let app = y_reader::YReader::default();
-    let mut native_options = eframe::NativeOptions::default();
-    native_options.initial_window_size = Some(Vec2::new(540., 960.));
-    // native_options.icon_data
-    native_options.icon_data = Some(eframe::epi::IconData {
-        width: icon_width,
-        height: icon_height,
-        rgba: icon.into_raw(),
-    });
+    let native_options = eframe::NativeOptions {
+        initial_window_size: Some(Vec2::new(540., 960.)),
+        icon_data: Some(eframe::epi::IconData {
+            width: icon_width,
+            height: icon_height,
+            rgba: icon.into_raw(),
+        }),
+        ..Default::default()
+    };
     eframe::run_native(Box::new(app), native_options);
 }
@@ -0,0 +1,193 @@
+use eframe::egui;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+/// The file mute rules are read from and saved to, inside the config directory.
+pub const CONFIG_FILE: &str = "mutes.toml";
+
+/// Stories and comments the user never wants to see.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MuteRules {
+    /// Case-insensitive substrings of story titles.
+    keywords: Vec<String>,
+    /// Case-insensitive regexes matched against story titles.
+    patterns: Vec<String>,
+    /// Sites, including their subdomains.
+    domains: Vec<String>,
+    /// Authors of stories and comments.
+    users: Vec<String>,
+    #[serde(skip)]
+    compiled: Vec<Regex>,
+    #[serde(skip)]
+    drafts: [String; 4],
+    #[serde(skip)]
+    error: Option<String>,
+}
+
+impl MuteRules {
+    /// Builds the title regexes. Call after loading; invalid patterns are skipped and reported.
+    pub fn compile(&mut self) {
+        self.error = None;
+        self.compiled = self
+            .patterns
+            .iter()
+            .filter_map(|p| match build(p) {
+                Ok(re) => Some(re),
+                Err(e) => {
+                    self.error = Some(format!("Ignoring invalid pattern {:?}: {}", p, e));
+                    None
+                }
+            })
+            .collect();
+    }
+
+    pub fn mutes_story(&self, title: Option<&str>, domain: Option<&str>, by: Option<&str>) -> bool {
+        if let Some(title) = title {
+            let lower = title.to_lowercase();
+            if self
+                .keywords
+                .iter()
+                .any(|k| lower.contains(&k.to_lowercase()))
+                || self.compiled.iter().any(|re| re.is_match(title))
+            {
+                return true;
+            }
+        }
+
+        if let Some(domain) = domain {
+            let domain = domain.to_lowercase();
+            if self.domains.iter().any(|d| {
+                let d = d.to_lowercase();
+                domain == d || domain.ends_with(&format!(".{}", d))
+            }) {
+                return true;
+            }
+        }
+
+        self.mutes_user(by)
+    }
+
+    pub fn mutes_user(&self, by: Option<&str>) -> bool {
+        by.map_or(false, |by| self.users.iter().any(|u| u == by))
+    }
+
+    /// Draws the rule lists with add and remove controls, returning whether anything changed.
+    pub fn settings_ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+
+        let lists = [
+            ("Title keywords", &mut self.keywords),
+            ("Title patterns (regex)", &mut self.patterns),
+            ("Domains", &mut self.domains),
+            ("Users", &mut self.users),
+        ];
+        egui::Grid::new("mutes").striped(true).show(ui, |ui| {
+            for (i, (label, list)) in lists.into_iter().enumerate() {
+                ui.label(label);
+                ui.vertical(|ui| {
+                    ui.horizontal_wrapped(|ui| {
+                        let mut removed = None;
+                        for (j, rule) in list.iter().enumerate() {
+                            if ui
+                                .small_button(rule)
+                                .on_hover_text("Click to remove")
+                                .clicked()
+                            {
+                                removed = Some(j);
+                            }
+                        }
+                        if let Some(j) = removed {
+                            list.remove(j);
+                            changed = true;
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        let draft = &mut self.drafts[i];
+                        let input = ui.add(egui::TextEdit::singleline(draft).desired_width(160.));
+                        let submitted =
+                            input.lost_focus() && ui.input().key_pressed(egui::Key::Enter);
+                        if (ui.small_button("Add").clicked() || submitted)
+                            && !draft.trim().is_empty()
+                        {
+                            let rule = draft.trim().to_string();
+                            // Patterns are checked here so a typo doesn't silently match nothing.
+                            match build(&rule).err().filter(|_| i == 1) {
+                                Some(e) => self.error = Some(e.to_string()),
+                                None if !list.contains(&rule) => {
+                                    list.push(rule);
+                                    draft.clear();
+                                    changed = true;
+                                }
+                                None => draft.clear(),
+                            }
+                        }
+                    });
+                });
+                ui.end_row();
+            }
+        });
+
+        if changed {
+            self.compile();
+        }
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::RED, error.as_str());
+        }
+
+        changed
+    }
+}
+
+fn build(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern).case_insensitive(true).build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(text: &str) -> MuteRules {
+        let mut rules: MuteRules = toml::from_str(text).unwrap();
+        rules.compile();
+        rules
+    }
+
+    #[test]
+    fn mutes_domains_and_their_subdomains() {
+        let rules = rules("domains = [\"Example.com\"]");
+        assert!(rules.mutes_story(None, Some("example.com"), None));
+        assert!(rules.mutes_story(None, Some("www.example.com"), None));
+        assert!(rules.mutes_story(None, Some("blog.EXAMPLE.com"), None));
+        assert!(!rules.mutes_story(None, Some("notexample.com"), None));
+        assert!(!rules.mutes_story(None, Some("example.com.au"), None));
+        assert!(!rules.mutes_story(None, None, None));
+    }
+
+    #[test]
+    fn mutes_titles_by_keyword_and_pattern() {
+        let rules = rules("keywords = [\"crypto\"]\npatterns = [\"^show hn: .*ai\\\\b\"]");
+        assert!(rules.mutes_story(Some("Why Crypto matters"), None, None));
+        assert!(rules.mutes_story(Some("Show HN: My AI app"), None, None));
+        assert!(!rules.mutes_story(Some("Ask HN: AI jobs?"), None, None));
+        assert!(!rules.mutes_story(Some("Show HN: Maintainer tools"), None, None));
+    }
+
+    #[test]
+    fn skips_and_reports_invalid_patterns() {
+        let rules = rules("patterns = [\"(unclosed\", \"rust\"]");
+        assert!(rules.error.as_deref().unwrap().contains("(unclosed"));
+        assert_eq!(rules.compiled.len(), 1);
+        assert!(rules.mutes_story(Some("Rust 2.0"), None, None));
+    }
+
+    #[test]
+    fn mutes_users_exactly() {
+        let rules = rules("users = [\"troll\"]");
+        assert!(rules.mutes_user(Some("troll")));
+        assert!(!rules.mutes_user(Some("trolley")));
+        assert!(!rules.mutes_user(None));
+        assert!(rules.mutes_story(Some("Anything"), None, Some("troll")));
+    }
+}
@@ -0,0 +1,172 @@
+use crate::keys::{Action, Keymap};
+use eframe::egui;
+
+/// What the user picked from the palette.
+pub enum Command {
+    Run(Action),
+    OpenItem(u32),
+}
+
+/// The Ctrl+K command palette: a filterable list of every action.
+#[derive(Default)]
+pub struct CommandPalette {
+    open: bool,
+    query: String,
+    selected: usize,
+}
+
+impl CommandPalette {
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.query.clear();
+        self.selected = 0;
+    }
+
+    /// Shows the palette if it is open, returning the command the user chose this frame.
+    /// `parse_item` turns the query into an item id when it looks like one.
+    pub fn show(
+        &mut self,
+        ctx: &egui::CtxRef,
+        keymap: &Keymap,
+        parse_item: impl Fn(&str) -> Option<u32>,
+    ) -> Option<Command> {
+        if !self.open {
+            return None;
+        }
+
+        let mut entries: Vec<(Command, String)> = Vec::new();
+        if let Some(id) = parse_item(&self.query) {
+            entries.push((Command::OpenItem(id), format!("Open item {}", id)));
+        }
+
+        let mut actions: Vec<(i32, Action)> = Action::ALL
+            .iter()
+            .filter(|action| **action != Action::CommandPalette)
+            .filter_map(|action| {
+                fuzzy_score(&self.query, action.description()).map(|s| (s, *action))
+            })
+            .collect();
+        actions.sort_by_key(|(score, _)| -score);
+        entries.extend(
+            actions
+                .into_iter()
+                .map(|(_, action)| (Command::Run(action), action.description().to_string())),
+        );
+
+        let input = ctx.input();
+        if input.key_pressed(egui::Key::ArrowDown) {
+            self.selected += 1;
+        }
+        if input.key_pressed(egui::Key::ArrowUp) {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        let confirmed = input.key_pressed(egui::Key::Enter);
+        let cancelled = input.key_pressed(egui::Key::Escape);
+        self.selected = self.selected.min(entries.len().saturating_sub(1));
+
+        let mut chosen = None;
+        egui::Window::new("Command palette")
+            .title_bar(false)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, egui::Vec2::new(0., 48.))
+            .show(ctx, |ui| {
+                let previous = self.query.clone();
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.query)
+                        .hint_text("Type a command, or an item id or URL")
+                        .desired_width(320.),
+                )
+                .request_focus();
+                if self.query != previous {
+                    self.selected = 0;
+                }
+
+                ui.separator();
+                for (i, (_, label)) in entries.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.selectable_label(i == self.selected, label).clicked() {
+                            chosen = Some(i);
+                        }
+                        if let Command::Run(action) = entries[i].0 {
+                            if let Some(binding) = keymap.bindings(action).first() {
+                                ui.with_layout(egui::Layout::right_to_left(), |ui| {
+                                    ui.code(binding.to_string());
+                                });
+                            }
+                        }
+                    });
+                }
+                if entries.is_empty() {
+                    ui.label(egui::RichText::new("No matching commands").weak());
+                }
+            });
+
+        if confirmed && !entries.is_empty() {
+            chosen = Some(self.selected);
+        }
+        if chosen.is_some() || cancelled {
+            self.open = false;
+        }
+        chosen.map(|i| entries.swap_remove(i).0)
+    }
+}
+
+/// Scores how well `query` matches `candidate` as a case-insensitive subsequence, favouring
+/// runs of consecutive characters and matches at the start of words. `None` if it doesn't
+/// match at all.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+
+    for q in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = position + candidate[position..].iter().position(|c| *c == q)?;
+        score += 1;
+        if previous.map_or(false, |p| p + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || candidate[found - 1] == ' ' {
+            score += 3;
+        }
+        score -= (found - position) as i32 / 4;
+        previous = Some(found);
+        position = found + 1;
+    }
+
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefix_beats_scattered_match() {
+        let prefix = fuzzy_score("exp", "Export thread").unwrap();
+        let scattered = fuzzy_score("exp", "Next page").unwrap();
+        assert!(prefix > scattered);
+        assert!(fuzzy_score("tog", "Toggle help") > fuzzy_score("tog", "Go to top"));
+    }
+
+    #[test]
+    fn word_starts_beat_middles() {
+        assert!(fuzzy_score("sh", "Show alerts") > fuzzy_score("sh", "Refresh"));
+    }
+
+    #[test]
+    fn ignores_case_and_spaces() {
+        assert_eq!(
+            fuzzy_score("Open Link", "open link"),
+            fuzzy_score("openlink", "OPEN LINK")
+        );
+    }
+
+    #[test]
+    fn non_match_is_none() {
+        assert_eq!(fuzzy_score("xyz", "Export thread"), None);
+        // Every character is there, but not in this order.
+        assert_eq!(fuzzy_score("daerht", "Open thread"), None);
+    }
+}
@@ -0,0 +1,351 @@
+use crate::app::Tab;
+use crate::fonts;
+use crate::theme::Theme;
+use crate::timestamp::{self, Clock};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// The file settings are read from and saved to, inside the config directory.
+pub const CONFIG_FILE: &str = "settings.toml";
+
+/// What clicking a link to another item on the site does.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum LinkBehavior {
+    /// Open the item in the reader, leaving other links to the browser.
+    InApp,
+    /// Send every link to the browser.
+    Browser,
+}
+
+/// User preferences, edited from the settings window.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    /// How long the feeds wait between refreshes.
+    pub refresh_seconds: u64,
+    /// How many stories each feed loads at a time.
+    pub page_size: usize,
+    /// The name of a built-in or user theme.
+    pub theme: String,
+    /// Multiplies the size of all text.
+    pub font_scale: f32,
+    /// Zooms the whole interface, on top of the display's own scaling.
+    pub ui_scale: f32,
+    /// A font file to use instead of the built-in one; empty for the default.
+    pub body_font: String,
+    pub monospace_font: String,
+    /// Font files tried, in order, for characters the others lack.
+    pub fallback_fonts: Vec<String>,
+    /// Whether to look for installed CJK and emoji fonts to fall back on.
+    pub system_fallbacks: bool,
+    pub links: LinkBehavior,
+    /// Show the open thread beside the story list when the window is wide enough.
+    pub split_pane: bool,
+    pub timestamps: timestamp::Style,
+    /// A strftime-style format for absolute times, e.g. `%Y-%m-%d %H:%M`.
+    pub time_format: String,
+    pub default_tab: Tab,
+    pub hide_dead_comments: bool,
+    pub expand_replies: bool,
+    /// With `expand_replies` on, replies nested deeper than this still start collapsed; zero
+    /// for no limit. Without it every reply starts collapsed and this has no effect.
+    pub collapse_depth: usize,
+    /// How far each level of a thread is indented, in points.
+    pub indent: f32,
+    pub prefetch_threads: bool,
+    /// Where favorites and upvoted lists are scraped from; point it at saved pages to test.
+    /// Only set in the settings file, and signing in is refused anywhere but the real site.
+    pub import_base_url: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            refresh_seconds: 60,
+            page_size: 50,
+            theme: Theme::default().name,
+            font_scale: 1.0,
+            ui_scale: 1.0,
+            body_font: String::new(),
+            monospace_font: String::new(),
+            fallback_fonts: Vec::new(),
+            system_fallbacks: true,
+            links: LinkBehavior::InApp,
+            split_pane: true,
+            timestamps: timestamp::Style::Relative,
+            time_format: timestamp::DEFAULT_FORMAT.to_string(),
+            default_tab: Tab::Top,
+            hide_dead_comments: false,
+            expand_replies: false,
+            collapse_depth: 0,
+            indent: 18.,
+            prefetch_threads: false,
+            import_base_url: "https://news.ycombinator.com".to_string(),
+        }
+    }
+}
+
+impl Settings {
+    /// The fonts to use at the current scale, and messages for any that couldn't be loaded.
+    pub fn fonts(&self) -> (egui::FontDefinitions, Vec<String>) {
+        let mut definitions = egui::FontDefinitions::default();
+        for (_, size) in definitions.family_and_size.values_mut() {
+            *size *= self.font_scale;
+        }
+
+        let mut errors = Vec::new();
+        let mut load = |path: &Path| match fonts::read(path) {
+            Ok(data) => Some(data),
+            Err(e) => {
+                errors.push(format!("Couldn't load {}: {}", path.display(), e));
+                None
+            }
+        };
+        let primaries = [
+            (egui::FontFamily::Proportional, &self.body_font),
+            (egui::FontFamily::Monospace, &self.monospace_font),
+        ];
+        for (family, path) in primaries {
+            if path.is_empty() {
+                continue;
+            }
+            if let Some(data) = load(Path::new(path)) {
+                fonts::set_primary(&mut definitions, family, path, data);
+            }
+        }
+
+        let mut fallbacks: Vec<&Path> = self
+            .fallback_fonts
+            .iter()
+            .filter(|path| !path.is_empty())
+            .map(Path::new)
+            .collect();
+        if self.system_fallbacks {
+            fallbacks.extend(fonts::system_fallbacks());
+        }
+        for path in fallbacks {
+            if let Some(data) = load(path) {
+                fonts::add_fallback(&mut definitions, &path.to_string_lossy(), data);
+            }
+        }
+
+        (definitions, errors)
+    }
+
+    /// Whether `other` would load the same fonts, so they needn't be rebuilt.
+    pub fn same_fonts(&self, other: &Self) -> bool {
+        self.font_scale == other.font_scale
+            && self.body_font == other.body_font
+            && self.monospace_font == other.monospace_font
+            && self.fallback_fonts == other.fallback_fonts
+            && self.system_fallbacks == other.system_fallbacks
+    }
+
+    /// Steps the interface scale up or down by `step`, within sensible limits.
+    pub fn zoom(&mut self, step: f32) {
+        self.ui_scale = ((self.ui_scale + step) * 10.).round().clamp(5., 30.) / 10.;
+    }
+
+    /// Draws every setting, returning whether anything changed.
+    pub fn ui(&mut self, ui: &mut egui::Ui, themes: &[Theme]) -> bool {
+        let before = self.clone();
+
+        ui.heading("Feeds");
+        egui::Grid::new("feed_settings")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Refresh every");
+                ui.add(
+                    egui::DragValue::new(&mut self.refresh_seconds)
+                        .clamp_range(10..=3600)
+                        .suffix(" s"),
+                );
+                ui.end_row();
+
+                ui.label("Stories per page");
+                ui.add(egui::DragValue::new(&mut self.page_size).clamp_range(10..=500));
+                ui.end_row();
+
+                ui.label("Start on");
+                egui::ComboBox::from_id_source("default_tab")
+                    .selected_text(self.default_tab.name())
+                    .show_ui(ui, |ui| {
+                        for tab in Tab::ALL {
+                            ui.selectable_value(&mut self.default_tab, tab, tab.name());
+                        }
+                    });
+                ui.end_row();
+            });
+        ui.separator();
+
+        ui.heading("Appearance");
+        egui::Grid::new("appearance_settings")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Theme");
+                egui::ComboBox::from_id_source("theme")
+                    .selected_text(self.theme.as_str())
+                    .show_ui(ui, |ui| {
+                        for theme in themes {
+                            ui.horizontal(|ui| {
+                                theme.swatch(ui);
+                                ui.selectable_value(
+                                    &mut self.theme,
+                                    theme.name.clone(),
+                                    theme.name.as_str(),
+                                );
+                            });
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Interface scale");
+                ui.add(egui::Slider::new(&mut self.ui_scale, 0.5..=3.0).fixed_decimals(1))
+                    .on_hover_text(
+                        "Ctrl+PgUp and Ctrl+PgDown (or Ctrl+ and Ctrl-) zoom, Ctrl+0 resets",
+                    );
+                ui.end_row();
+
+                ui.label("Font scale");
+                ui.add(egui::Slider::new(&mut self.font_scale, 0.75..=2.0).fixed_decimals(2));
+                ui.end_row();
+
+                ui.label("Body font");
+                deferred_edit(ui, "body_font", &mut self.body_font, "Built-in", false);
+                ui.end_row();
+
+                ui.label("Monospace font");
+                deferred_edit(
+                    ui,
+                    "monospace_font",
+                    &mut self.monospace_font,
+                    "Built-in",
+                    false,
+                );
+                ui.end_row();
+
+                ui.label("Fallback fonts");
+                let mut fallbacks = self.fallback_fonts.join("\n");
+                deferred_edit(
+                    ui,
+                    "fallback_fonts",
+                    &mut fallbacks,
+                    "One font file per line",
+                    true,
+                );
+                self.fallback_fonts = fallbacks
+                    .lines()
+                    .map(str::trim)
+                    .filter(|path| !path.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                ui.end_row();
+
+                ui.label("");
+                ui.checkbox(
+                    &mut self.system_fallbacks,
+                    "Fall back on installed CJK and emoji fonts",
+                );
+                ui.end_row();
+
+                ui.label("Layout");
+                ui.checkbox(
+                    &mut self.split_pane,
+                    "Threads beside the story list on wide windows",
+                );
+                ui.end_row();
+
+                ui.label("Timestamps");
+                ui.horizontal(|ui| {
+                    use timestamp::Style;
+                    ui.selectable_value(&mut self.timestamps, Style::Relative, "Relative");
+                    ui.selectable_value(&mut self.timestamps, Style::Absolute, "Absolute");
+                    ui.selectable_value(&mut self.timestamps, Style::Both, "Both");
+                });
+                ui.end_row();
+
+                ui.label("Time format");
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.time_format)
+                            .hint_text(timestamp::DEFAULT_FORMAT)
+                            .desired_width(140.),
+                    );
+                    if timestamp::valid_format(&self.time_format) {
+                        let clock = Clock::new(0, self.timestamps, &self.time_format);
+                        ui.small(format!("e.g. {}", clock.absolute(1_640_995_200)));
+                    } else {
+                        ui.colored_label(egui::Color32::RED, "Not a valid format");
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Links to other items");
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.links, LinkBehavior::InApp, "Open in reader");
+                    ui.selectable_value(&mut self.links, LinkBehavior::Browser, "Open in browser");
+                });
+                ui.end_row();
+            });
+        ui.separator();
+
+        ui.heading("Comments");
+        ui.checkbox(&mut self.hide_dead_comments, "Hide dead comments");
+        ui.checkbox(&mut self.expand_replies, "Expand replies by default");
+        ui.add_enabled_ui(self.expand_replies, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Collapse replies below level");
+                ui.add(egui::DragValue::new(&mut self.collapse_depth).clamp_range(0..=20))
+                    .on_hover_text("0 expands every level")
+                    .on_disabled_hover_text("Only applies when replies are expanded by default");
+            });
+        });
+        ui.checkbox(
+            &mut self.prefetch_threads,
+            "Prefetch whole threads when opened",
+        );
+        ui.horizontal(|ui| {
+            ui.label("Indent replies by");
+            ui.add(
+                egui::DragValue::new(&mut self.indent)
+                    .clamp_range(0.0..=48.0)
+                    .suffix(" pt"),
+            );
+        });
+        if ui.button("Reset to defaults").clicked() {
+            *self = Self::default();
+        }
+
+        *self != before
+    }
+}
+
+/// A text field that only writes back to `value` once editing finishes, so that fonts aren't
+/// reloaded on every keystroke.
+fn deferred_edit(
+    ui: &mut egui::Ui,
+    id_source: &str,
+    value: &mut String,
+    hint: &str,
+    multiline: bool,
+) {
+    let id = ui.make_persistent_id(id_source);
+    let mut draft = ui
+        .memory()
+        .data
+        .get_temp::<String>(id)
+        .unwrap_or_else(|| value.clone());
+    let edit = if multiline {
+        egui::TextEdit::multiline(&mut draft).desired_rows(2)
+    } else {
+        egui::TextEdit::singleline(&mut draft)
+    };
+    let response = ui.add(edit.hint_text(hint).desired_width(240.));
+    if response.lost_focus() {
+        *value = draft.trim().to_string();
+        ui.memory().data.remove::<String>(id);
+    } else if response.has_focus() {
+        ui.memory().data.insert_temp(id, draft);
+    }
+}
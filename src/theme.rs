@@ -0,0 +1,176 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+/// The file user themes are read from, inside the config directory.
+pub const CONFIG_FILE: &str = "themes.toml";
+
+/// Colors are `[r, g, b]`, so a theme is easy to write by hand:
+///
+/// ```toml
+/// [[themes]]
+/// name = "Solarized"
+/// dark = true
+/// accent = [181, 137, 0]
+/// link = [38, 139, 210]
+/// background = [0, 43, 54]
+/// text = [147, 161, 161]
+/// depth_colors = [[38, 139, 210], [42, 161, 152], [133, 153, 0]]
+/// ```
+///
+/// Anything left out is taken from the built-in dark theme.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Theme {
+    pub name: String,
+    /// Whether to start from egui's dark visuals rather than its light ones.
+    pub dark: bool,
+    /// Selected tabs, buttons and text.
+    pub accent: [u8; 3],
+    /// Text drawn on top of the accent color.
+    pub accent_text: [u8; 3],
+    pub link: [u8; 3],
+    /// Panels and windows; egui's own when unset.
+    pub background: Option<[u8; 3]>,
+    /// Ordinary text; egui's own when unset.
+    pub text: Option<[u8; 3]>,
+    /// The guide beside each level of replies, repeating for deeper threads.
+    pub depth_colors: Vec<[u8; 3]>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            name: "Dark".to_string(),
+            dark: true,
+            accent: [215, 101, 46],
+            accent_text: [255, 255, 255],
+            link: [205, 91, 36],
+            background: None,
+            text: None,
+            depth_colors: vec![
+                [205, 91, 36],
+                [74, 137, 199],
+                [96, 168, 92],
+                [163, 109, 196],
+                [196, 168, 64],
+                [72, 168, 168],
+            ],
+        }
+    }
+}
+
+/// The contents of the themes file.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct UserThemes {
+    pub themes: Vec<Theme>,
+}
+
+/// The themes that ship with the reader, the first being the default.
+pub fn builtin() -> Vec<Theme> {
+    vec![
+        Theme::default(),
+        Theme {
+            name: "Light".to_string(),
+            dark: false,
+            accent: [215, 101, 46],
+            link: [181, 72, 20],
+            depth_colors: vec![
+                [181, 72, 20],
+                [38, 97, 156],
+                [52, 122, 48],
+                [120, 64, 156],
+                [150, 118, 16],
+                [28, 120, 120],
+            ],
+            ..Theme::default()
+        },
+        Theme {
+            name: "High contrast".to_string(),
+            dark: true,
+            accent: [255, 214, 0],
+            accent_text: [0, 0, 0],
+            link: [0, 220, 255],
+            background: Some([0, 0, 0]),
+            text: Some([255, 255, 255]),
+            depth_colors: vec![[255, 214, 0], [0, 220, 255], [255, 255, 255]],
+        },
+        Theme {
+            name: "HN classic".to_string(),
+            dark: false,
+            accent: [255, 102, 0],
+            link: [130, 130, 130],
+            background: Some([246, 246, 239]),
+            text: Some([0, 0, 0]),
+            depth_colors: vec![[255, 102, 0], [190, 190, 180]],
+            ..Theme::default()
+        },
+    ]
+}
+
+/// The built-in themes followed by the user's, a user theme replacing a built-in one of the
+/// same name.
+pub fn all(user: UserThemes) -> Vec<Theme> {
+    let mut themes = builtin();
+    for theme in user.themes {
+        match themes.iter_mut().find(|t| t.name == theme.name) {
+            Some(existing) => *existing = theme,
+            None => themes.push(theme),
+        }
+    }
+    themes
+}
+
+fn color([r, g, b]: [u8; 3]) -> egui::Color32 {
+    egui::Color32::from_rgb(r, g, b)
+}
+
+impl Theme {
+    pub fn visuals(&self) -> egui::Visuals {
+        let mut visuals = if self.dark {
+            egui::Visuals::dark()
+        } else {
+            egui::Visuals::light()
+        };
+        visuals.selection = egui::style::Selection {
+            bg_fill: color(self.accent),
+            stroke: egui::Stroke::new(1., color(self.accent_text)),
+        };
+        visuals.hyperlink_color = color(self.link);
+        if let Some(background) = self.background {
+            visuals.widgets.noninteractive.bg_fill = color(background);
+        }
+        if let Some(text) = self.text {
+            visuals.widgets.noninteractive.fg_stroke.color = color(text);
+        }
+        visuals
+    }
+
+    /// The built-in theme the light/dark toggle switches to from this one.
+    pub fn opposite(&self) -> String {
+        if self.dark { "Light" } else { "Dark" }.to_string()
+    }
+
+    /// The guide for replies `depth` levels down, or `None` to keep egui's faint line.
+    pub fn depth_stroke(&self, depth: usize) -> Option<egui::Stroke> {
+        if self.depth_colors.is_empty() {
+            return None;
+        }
+        let rgb = self.depth_colors[depth % self.depth_colors.len()];
+        Some(egui::Stroke::new(2., color(rgb)))
+    }
+
+    /// A row of small squares showing the theme's colors.
+    pub fn swatch(&self, ui: &mut egui::Ui) {
+        let mut colors: Vec<[u8; 3]> = self.background.into_iter().collect();
+        colors.extend([self.accent, self.link]);
+        colors.extend(&self.depth_colors);
+        ui.horizontal(|ui| {
+            ui.spacing_mut().item_spacing.x = 2.;
+            for rgb in colors {
+                let (rect, _) = ui.allocate_exact_size(egui::vec2(10., 10.), egui::Sense::hover());
+                ui.painter().rect_filled(rect, 2., color(rgb));
+            }
+        });
+    }
+}
@@ -0,0 +1,75 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{Local, TimeZone};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use time_humanize::HumanTime;
+
+pub const DEFAULT_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// How item times are shown. Whichever form isn't shown appears on hover.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Style {
+    /// "3 hours ago"
+    Relative,
+    /// In the local time zone, using the configured format.
+    Absolute,
+    Both,
+}
+
+/// Whether `format` is a valid strftime-style format, which chrono would otherwise panic on.
+pub fn valid_format(format: &str) -> bool {
+    !StrftimeItems::new(format).any(|item| matches!(item, Item::Error))
+}
+
+/// Formats times against a single reading of the clock, taken once per frame, so that every
+/// timestamp on screen agrees on what "now" is.
+pub struct Clock {
+    now: u64,
+    style: Style,
+    format: String,
+}
+
+impl Clock {
+    /// Falls back to the default format if `format` is invalid.
+    pub fn new(now: u64, style: Style, format: &str) -> Self {
+        let format = if valid_format(format) {
+            format
+        } else {
+            DEFAULT_FORMAT
+        };
+        Self {
+            now,
+            style,
+            format: format.to_string(),
+        }
+    }
+
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    pub fn relative(&self, secs: u64) -> String {
+        HumanTime::from_seconds(secs as i64 - self.now as i64).to_string()
+    }
+
+    pub fn absolute(&self, secs: u64) -> String {
+        Local
+            .timestamp_opt(secs as i64, 0)
+            .earliest()
+            .map(|time| time.format(&self.format).to_string())
+            .unwrap_or_default()
+    }
+
+    /// A label for `secs` in the chosen style.
+    pub fn label(&self, ui: &mut egui::Ui, secs: u64) -> egui::Response {
+        match self.style {
+            Style::Relative => ui
+                .label(self.relative(secs))
+                .on_hover_text(self.absolute(secs)),
+            Style::Absolute => ui
+                .label(self.absolute(secs))
+                .on_hover_text(self.relative(secs)),
+            Style::Both => ui.label(format!("{} ({})", self.relative(secs), self.absolute(secs))),
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+/// The file the watch list is read from and saved to, inside the config directory.
+pub const CONFIG_FILE: &str = "watch.toml";
+
+/// Badge color for stories matching a watched term.
+pub const BADGE_COLOR: egui::Color32 = egui::Color32::from_rgb(46, 140, 87);
+
+/// Terms to call out in story titles, the opposite of muting.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WatchList {
+    pub terms: Vec<String>,
+    #[serde(skip)]
+    draft: String,
+}
+
+impl WatchList {
+    /// Draws the terms with add and remove controls, returning whether anything changed.
+    pub fn settings_ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+
+        ui.horizontal_wrapped(|ui| {
+            let mut removed = None;
+            for (i, term) in self.terms.iter().enumerate() {
+                if ui
+                    .small_button(term)
+                    .on_hover_text("Click to remove")
+                    .clicked()
+                {
+                    removed = Some(i);
+                }
+            }
+            if let Some(i) = removed {
+                self.terms.remove(i);
+                changed = true;
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let input = ui.add(
+                egui::TextEdit::singleline(&mut self.draft)
+                    .hint_text("Word or phrase")
+                    .desired_width(160.),
+            );
+            let submitted = input.lost_focus() && ui.input().key_pressed(egui::Key::Enter);
+            if (ui.small_button("Add").clicked() || submitted) && !self.draft.trim().is_empty() {
+                let term = self.draft.trim().to_string();
+                if !self.terms.contains(&term) {
+                    self.terms.push(term);
+                    changed = true;
+                }
+                self.draft.clear();
+            }
+        });
+
+        changed
+    }
+}
+
+/// The colored label shown next to a story matching `term`.
+pub fn badge(term: &str) -> egui::RichText {
+    egui::RichText::new(format!(" {} ", term))
+        .color(egui::Color32::WHITE)
+        .background_color(BADGE_COLOR)
+}
+
+/// The first of `terms` that appears in `title` as a whole word, ignoring case, so watching
+/// "Rust" doesn't flag "Trust".
+pub fn matching_term<'a>(terms: &'a [String], title: &str) -> Option<&'a str> {
+    let title = title.to_lowercase();
+    let is_word = |c: Option<char>| c.map_or(false, char::is_alphanumeric);
+
+    terms.iter().map(String::as_str).find(|term| {
+        let term = term.to_lowercase();
+        !term.is_empty()
+            && title.match_indices(&term).any(|(start, _)| {
+                let end = start + term.len();
+                !is_word(title[..start].chars().next_back())
+                    && !is_word(title[end..].chars().next())
+            })
+    })
+}